@@ -1,14 +1,48 @@
+use std::{net::SocketAddr, time::Duration};
+
 use clap::{Parser, ValueEnum};
+use reqwest::Method;
+
+use crate::{assertions::Assertion, load::ThinkTime, metrics::MetricsRange, thresholds::Threshold};
+
+/// Any HTTP method: the standard ones in any case, or a custom token such as `PROPFIND`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpMethod(pub Method);
+
+impl std::str::FromStr for HttpMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let standard = [
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::HEAD,
+            Method::OPTIONS,
+            Method::TRACE,
+            Method::CONNECT,
+        ];
 
-use crate::metrics::MetricsRange;
+        let method = match standard.into_iter().find(|m| m.as_str().eq_ignore_ascii_case(s)) {
+            Some(method) => method,
+            None => Method::from_bytes(s.as_bytes()).map_err(|_| format!("Invalid HTTP method '{}'", s))?,
+        };
 
-#[derive(Debug, Clone, ValueEnum)]
-pub enum HttpMethod {
-    Get,
-    Post,
-    Patch,
-    Put,
-    Delete,
+        // CONNECT opens a tunnel through a proxy rather than requesting a resource, so there is no response to measure.
+        if method == Method::CONNECT {
+            return Err("CONNECT is not supported; use --proxy to send requests through a proxy".to_string());
+        }
+
+        Ok(HttpMethod(method))
+    }
+}
+
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
@@ -20,38 +54,129 @@ pub enum LoadPattern {
     Step,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DataStrategy {
+    /// Rows in file order, shared by all workers
+    Sequential,
+    /// A random row for every iteration
+    Random,
+    /// One row per worker, kept for the worker's lifetime
+    Unique,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DataExhausted {
+    /// Start over from the first row
+    Recycle,
+    /// End the test
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum BodyMode {
+    /// Leave the body unread unless an assertion or extraction needs it
+    Skip,
+    /// Read the whole body and discard it as it arrives
+    Stream,
+    /// Read the whole body into memory, like a client that uses it
+    Buffer,
+}
+
+/// TLS 1.3 cannot be required with the native TLS backend, so it is not offered.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum TlsVersion {
+    #[value(name = "1.0")]
+    Tls1_0,
+    #[value(name = "1.1")]
+    Tls1_1,
+    #[value(name = "1.2")]
+    Tls1_2,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SummaryFormat {
+    Text,
+    Markdown,
+}
+
 #[derive(Debug, Parser, Clone)]
 #[clap(version, about = "Simple HTTP stress tester")]
 pub struct TesterArgs {
-    /// HTTP method to use
+    /// HTTP method to use: GET, POST, PUT, PATCH, DELETE, HEAD, OPTIONS, TRACE or a custom verb
     #[arg(
         long,
         short = 'X',
-        default_value = "get",
-        ignore_case = true
+        default_value = "GET",
+        value_parser
     )]
     pub method: HttpMethod,
 
     /// Target URL for the stress test
-    #[arg(long, short)]
-    pub url: String,
+    #[arg(long, short, required_unless_present = "scenario")]
+    pub url: Option<String>,
+
+    /// Scenario file (YAML or TOML) with weighted requests; replaces --url, --method, --headers and --data
+    #[arg(long, conflicts_with = "url")]
+    pub scenario: Option<String>,
 
     /// HTTP headers in 'Key: Value' format (repeatable)
     #[arg(long, short = 'H', value_parser = parse_header)]
     pub headers: Vec<(String, String)>,
 
-    /// Request body data (for POST/PUT)
+    /// Request body data (for POST/PUT), or '@path' to read it from a file. URL, header values and body may use placeholders such as '{{uuid}}'
     #[arg(long, short, default_value = "")]
     pub data: String,
 
+    /// Request body sent byte for byte without placeholders, or '@path' to read it from a file
+    #[arg(long = "data-binary", conflicts_with = "data")]
+    pub data_binary: Option<String>,
+
+    /// URL-encoded form field in 'name=value' format (repeatable)
+    #[arg(long = "data-urlencode", value_parser = parse_field, conflicts_with_all = ["data", "data_binary"])]
+    pub form_fields: Vec<(String, String)>,
+
+    /// Multipart form field in 'name=value' format, or 'name=@path' to upload a file (repeatable)
+    #[arg(long = "form", short = 'F', value_parser = parse_field, conflicts_with_all = ["data", "data_binary", "form_fields"])]
+    pub multipart: Vec<(String, String)>,
+
+    /// CSV file with a header row, or JSON Lines file (.jsonl/.ndjson), whose columns are available as template variables
+    #[arg(long = "data-file")]
+    pub data_file: Option<String>,
+
+    /// How rows are taken from --data-file
+    #[arg(long = "data-strategy", value_enum, default_value = "sequential")]
+    pub data_strategy: DataStrategy,
+
+    /// What happens once every row of --data-file was used (ignored for the random strategy)
+    #[arg(long = "data-exhausted", value_enum, default_value = "recycle")]
+    pub data_exhausted: DataExhausted,
+
     /// Duration of test (seconds)
     #[arg(long = "duration", short = 't', default_value = "30")]
     pub target_duration: u64,
 
+    /// Stop after this many requests in total; --duration still caps the run
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub requests: Option<u64>,
+
+    /// Stop each task after this many iterations and end the test once all tasks finished; --duration still caps the run.
+    /// Not available with the spike and wave load patterns
+    #[arg(long = "iterations-per-worker", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "rate")]
+    pub iterations_per_worker: Option<u64>,
+
     /// Expected HTTP status code
     #[arg(long = "status", short = 's', default_value = "200")]
     pub expected_status_code: u16,
 
+    /// Check on every response, e.g. 'status:2xx', 'header:Content-Type~json', 'body:ok',
+    /// 'body-regex:^ok', 'json:$.status=ok', 'max-size:64kb' (repeatable). A status check replaces --status
+    #[arg(long = "assert", short = 'A', value_parser)]
+    pub assertions: Vec<Assertion>,
+
+    /// How response bodies are read. Download time and bytes received are only measured when they are read
+    #[arg(long = "body", value_enum, default_value = "stream")]
+    pub body_mode: BodyMode,
+
     /// Path to save charts to
     #[arg(long, short = 'c', default_value = "./charts")]
     pub charts_path: String,
@@ -60,11 +185,104 @@ pub struct TesterArgs {
     #[arg(long, short = 'n')]
     pub no_charts: bool,
 
+    /// Print progress lines to stderr instead of the interactive dashboard; implied when stdout is not a terminal
+    #[arg(long = "no-tui")]
+    pub no_tui: bool,
+
+    /// How often a progress line is printed without the dashboard, e.g. '10s' (default: 5s)
+    #[arg(long = "progress-interval", value_parser = parse_duration, default_value = "5s")]
+    pub progress_interval: Duration,
+
+    /// Write a JSON summary of the run to this path
+    #[arg(long = "output-json", short = 'o')]
+    pub output_json: Option<String>,
+
+    /// Print a summary of the run to stdout in the given format
+    #[arg(long, value_enum)]
+    pub summary: Option<SummaryFormat>,
+
+    /// Pass/fail condition on the final metrics, e.g. 'p99<250ms', 'error_rate<1%', 'rps>500' (repeatable)
+    #[arg(long = "threshold", short = 'T', value_parser)]
+    pub thresholds: Vec<Threshold>,
+
     /// Proxy URL (optional)
     #[arg(long, short = 'p')]
     pub proxy_url: Option<String>,
 
-    /// Max number of concurrent tasks for each request worker (default: 1000)
+    /// Total time allowed per request, e.g. '500ms', '10s', '1m' (default: 10s)
+    #[arg(long, value_parser = parse_duration, default_value = "10s")]
+    pub timeout: Duration,
+
+    /// Once the test stops, how long requests in flight may still finish before they are cancelled (default: 5s)
+    #[arg(long = "drain-timeout", value_parser = parse_duration, default_value = "5s")]
+    pub drain_timeout: Duration,
+
+    /// Time allowed to establish a connection (default: no limit besides --timeout)
+    #[arg(long = "connect-timeout", value_parser = parse_duration)]
+    pub connect_timeout: Option<Duration>,
+
+    /// How long idle connections stay in the pool (default: 90s)
+    #[arg(long = "pool-idle-timeout", value_parser = parse_duration)]
+    pub pool_idle_timeout: Option<Duration>,
+
+    /// Max idle connections kept per host (default: unlimited)
+    #[arg(long = "pool-max-idle-per-host")]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Interval for TCP keepalive probes (default: disabled)
+    #[arg(long = "tcp-keepalive", value_parser = parse_duration)]
+    pub tcp_keepalive: Option<Duration>,
+
+    /// Enable Nagle's algorithm instead of setting TCP_NODELAY
+    #[arg(long = "no-tcp-nodelay")]
+    pub no_tcp_nodelay: bool,
+
+    /// PEM file with CA certificates to trust in addition to the system ones
+    #[arg(long)]
+    pub cacert: Option<String>,
+
+    /// Client certificate for mutual TLS: PEM (requires --key) or PKCS#12 (.p12/.pfx)
+    #[arg(long)]
+    pub cert: Option<String>,
+
+    /// PKCS#8 PEM private key for --cert
+    #[arg(long, requires = "cert")]
+    pub key: Option<String>,
+
+    /// Password for a PKCS#12 --cert
+    #[arg(long = "cert-password", requires = "cert", default_value = "")]
+    pub cert_password: String,
+
+    /// Skip certificate and hostname verification
+    #[arg(long, short = 'k')]
+    pub insecure: bool,
+
+    /// Minimum TLS version to accept
+    #[arg(long = "tls-min-version", value_enum)]
+    pub tls_min_version: Option<TlsVersion>,
+
+    /// Connect to 'addr' for 'host', e.g. 'example.com:443:127.0.0.1' (repeatable). TLS still uses 'host' for SNI and verification.
+    /// Unlike curl the port is ignored: the override applies to every port of 'host', and the port comes from the URL
+    #[arg(long, value_parser = parse_resolve)]
+    pub resolve: Vec<(String, SocketAddr)>,
+
+    /// Only use HTTP/1.1 (default: HTTP/2 when the server offers it over TLS, HTTP/1.1 otherwise)
+    #[arg(long = "http1.1", conflicts_with_all = ["http2", "http3"])]
+    pub http1_only: bool,
+
+    /// Only use HTTP/2, with prior knowledge on plain HTTP (h2c)
+    #[arg(long = "http2", conflicts_with = "http3")]
+    pub http2: bool,
+
+    /// Use HTTP/3 (experimental, not supported by this build yet)
+    #[arg(long = "http3")]
+    pub http3: bool,
+
+    /// Open a new connection for every request and ask the server to close it ('Connection: close')
+    #[arg(long = "no-keepalive", conflicts_with_all = ["pool_idle_timeout", "pool_max_idle_per_host"])]
+    pub no_keepalive: bool,
+
+    /// Max number of concurrent tasks, also the peak for load patterns (default: 1000)
     #[arg(long, short = 'm', default_value = "1000")]
     pub max_tasks: usize,
 
@@ -76,6 +294,39 @@ pub struct TesterArgs {
     #[arg(long = "spawn-interval", short = 'i', default_value = "100")]
     pub tick_interval: u64,
 
+    /// Pause of each task after every iteration: '0', a fixed '250ms', uniform '100ms-500ms' or
+    /// exponential with a mean 'exp:200ms' (default: 0). Ignored when --rate is set
+    #[arg(long = "think-time", value_parser, default_value = "0")]
+    pub think_time: ThinkTime,
+
+    /// Target request rate (requests per second); launches requests on a fixed schedule instead of spawning tasks
+    #[arg(long, short = 'R', value_parser = clap::value_parser!(u64).range(1..))]
+    pub rate: Option<u64>,
+
+    /// Max number of in-flight requests when --rate is set (default: --max-tasks)
+    #[arg(long = "max-in-flight")]
+    pub max_in_flight: Option<usize>,
+
+    /// Load pattern driving the number of active tasks over time
+    #[arg(long = "load-pattern", short = 'l', value_enum, default_value = "ramp-up")]
+    pub load_pattern: LoadPattern,
+
+    /// Baseline number of tasks for the spike, wave and step patterns (default: 0)
+    #[arg(long = "base-tasks", default_value = "0")]
+    pub base_tasks: usize,
+
+    /// Length of a wave cycle, spike interval or step (seconds) (default: 10)
+    #[arg(long = "pattern-period", default_value = "10")]
+    pub pattern_period: u64,
+
+    /// Number of tasks added per step for the step pattern (default: 10)
+    #[arg(long = "step-size", default_value = "10")]
+    pub step_size: usize,
+
+    /// How long each spike lasts for the spike pattern (seconds) (default: 2)
+    #[arg(long = "spike-duration", default_value = "2")]
+    pub spike_duration: u64,
+
     /// Range, in seconds, of metrics to collect for charts (e.g., 10-30)
     #[arg(long = "metrics-range", short = 'M', value_parser, required = false)]
     pub metrics_range: Option<MetricsRange>,
@@ -90,3 +341,43 @@ fn parse_header(s: &str) -> Result<(String, String), String> {
     let value = parts[1].trim().to_string();
     Ok((key, value))
 }
+
+/// Parses '250ms', '10s' or '2m'; a plain number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let invalid = || format!("Invalid duration '{}'. Expected e.g. '500ms', '10s' or '1m'", s);
+
+    let (number, unit_secs) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else if let Some(mins) = s.strip_suffix('m') {
+        (mins, 60.0)
+    } else {
+        (s, 1.0)
+    };
+
+    let value: f64 = number.trim().parse().map_err(|_| invalid())?;
+    Duration::try_from_secs_f64(value * unit_secs).map_err(|_| invalid())
+}
+
+fn parse_resolve(s: &str) -> Result<(String, SocketAddr), String> {
+    let invalid = || format!("Invalid resolve entry '{}'. Expected 'host:port:addr'", s);
+
+    let mut parts = s.splitn(3, ':');
+    let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    let addr: std::net::IpAddr = addr.trim_start_matches('[').trim_end_matches(']').parse().map_err(|_| invalid())?;
+
+    Ok((host.to_string(), SocketAddr::new(addr, port)))
+}
+
+fn parse_field(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+        _ => Err(format!("Invalid field format: '{}'. Expected 'name=value'", s)),
+    }
+}
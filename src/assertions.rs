@@ -0,0 +1,168 @@
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+use crate::extract::JsonPath;
+
+#[derive(Debug, Clone)]
+enum Check {
+    /// Inclusive range of accepted status codes
+    Status { min: u16, max: u16 },
+    /// Header is present, and equal to or containing `value` when set
+    Header { name: String, value: Option<String>, contains: bool },
+    BodyContains(String),
+    BodyRegex(Regex),
+    /// JSON path matches, and its value equals `value` when set
+    Json { path: JsonPath, value: Option<String> },
+    MaxBodySize(usize),
+}
+
+/// A check on every response, e.g. `status:2xx`, `header:Content-Type~json`, `body:ok`,
+/// `body-regex:^\{.*\}$`, `json:$.status=ok` or `max-size:64kb`.
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    /// The expression as written, used to count failures
+    name: &'static str,
+    check: Check,
+}
+
+fn parse_status(value: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("Invalid status '{}'. Expected e.g. '200', '2xx' or '200-299'", value);
+
+    if let Some(class) = value.strip_suffix("xx") {
+        let class: u16 = class.parse().map_err(|_| invalid())?;
+        return if (1..=5).contains(&class) { Ok((class * 100, class * 100 + 99)) } else { Err(invalid()) };
+    }
+
+    match value.split_once('-') {
+        Some((min, max)) => {
+            let min = min.trim().parse().map_err(|_| invalid())?;
+            let max = max.trim().parse().map_err(|_| invalid())?;
+            if min <= max { Ok((min, max)) } else { Err(invalid()) }
+        }
+        None => {
+            let code = value.parse().map_err(|_| invalid())?;
+            Ok((code, code))
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    let lower = value.to_lowercase();
+    let (number, multiplier) = if let Some(n) = lower.strip_suffix("kb") {
+        (n, 1024)
+    } else if let Some(n) = lower.strip_suffix("mb") {
+        (n, 1024 * 1024)
+    } else {
+        (lower.strip_suffix('b').unwrap_or(&lower), 1)
+    };
+
+    let n = number
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid size '{}'. Expected bytes or a kb/mb suffix", value))?;
+
+    n.checked_mul(multiplier).ok_or_else(|| format!("Size '{}' is too large", value))
+}
+
+impl std::str::FromStr for Assertion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid assertion '{}'. Expected '<kind>:<value>', e.g. 'status:2xx'", s))?;
+
+        let check = match kind.trim().to_lowercase().as_str() {
+            "status" => {
+                let (min, max) = parse_status(value.trim())?;
+                Check::Status { min, max }
+            }
+            "header" => {
+                let (name, value, contains) = match value.find(['=', '~']) {
+                    Some(i) => (&value[..i], Some(value[i + 1..].to_string()), &value[i..=i] == "~"),
+                    None => (value, None, false),
+                };
+                Check::Header { name: name.trim().to_string(), value, contains }
+            }
+            "body" => Check::BodyContains(value.to_string()),
+            "body-regex" => Check::BodyRegex(
+                Regex::new(value).map_err(|e| format!("Invalid regex in assertion '{}': {}", s, e))?,
+            ),
+            "json" => {
+                let (path, value) = match value.split_once('=') {
+                    Some((path, value)) => (path, Some(value.to_string())),
+                    None => (value, None),
+                };
+                Check::Json { path: path.parse()?, value }
+            }
+            "max-size" => Check::MaxBodySize(parse_size(value.trim())?),
+            other => {
+                return Err(format!(
+                    "Unknown assertion kind '{}'. Expected one of: status, header, body, body-regex, json, max-size",
+                    other
+                ))
+            }
+        };
+
+        Ok(Assertion {
+            // Failures are counted per assertion in `Outcome`, which is `Copy`. Assertions are
+            // only parsed at startup, so leaking the expression is bounded.
+            name: Box::leak(s.to_string().into_boxed_str()),
+            check,
+        })
+    }
+}
+
+impl Assertion {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_status(&self) -> bool {
+        matches!(self.check, Check::Status { .. })
+    }
+
+    /// Whether the response body has to be downloaded to evaluate this assertion.
+    pub fn needs_body(&self) -> bool {
+        matches!(
+            self.check,
+            Check::BodyContains(_) | Check::BodyRegex(_) | Check::Json { .. } | Check::MaxBodySize(_)
+        )
+    }
+
+    pub fn needs_json(&self) -> bool {
+        matches!(self.check, Check::Json { .. })
+    }
+
+    /// Evaluates status and header checks. Body checks always pass here.
+    pub fn check_head(&self, status: u16, headers: &HeaderMap) -> bool {
+        match &self.check {
+            Check::Status { min, max } => (*min..=*max).contains(&status),
+            Check::Header { name, value, contains } => {
+                let found = headers.get_all(name.as_str()).iter().filter_map(|v| v.to_str().ok()).collect::<Vec<_>>();
+                match value {
+                    None => !found.is_empty(),
+                    Some(expected) if *contains => found.iter().any(|v| v.contains(expected.as_str())),
+                    Some(expected) => found.iter().any(|v| v == expected),
+                }
+            }
+            _ => true,
+        }
+    }
+
+    /// Evaluates body checks. `json` is the parsed body, if it is valid JSON.
+    pub fn check_body(&self, body: &[u8], json: Option<&Value>) -> bool {
+        match &self.check {
+            Check::BodyContains(text) => String::from_utf8_lossy(body).contains(text.as_str()),
+            Check::BodyRegex(regex) => regex.is_match(&String::from_utf8_lossy(body)),
+            Check::Json { path, value } => match (json.and_then(|json| path.extract(json)), value) {
+                (Some(actual), Some(expected)) => actual == *expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+            Check::MaxBodySize(max) => body.len() <= *max,
+            _ => true,
+        }
+    }
+}
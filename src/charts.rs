@@ -1,17 +1,33 @@
-use std::{collections::BTreeMap, path::Path};
+use std::{collections::BTreeSet, path::Path};
 
 use plotters::prelude::*;
 use tokio::fs;
 use tracing::{error, info};
 
-use crate::{args::TesterArgs, metrics::Metrics};
+use crate::{args::TesterArgs, metrics::{MetricsAggregate, MetricsRange, SecondSummary}};
 
 pub async fn plot_metrics(
-    metrics: &Vec<Metrics>,
+    aggregate: &MetricsAggregate,
     args: &TesterArgs,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path = &args.charts_path;
-    let expected_status_code = &args.expected_status_code;
+
+    let metrics: Vec<SecondSummary> = aggregate
+        .seconds
+        .iter()
+        .filter(|s| match &args.metrics_range {
+            Some(MetricsRange(range)) => range.contains(&s.second),
+            None => true,
+        })
+        .cloned()
+        .collect();
+
+    if metrics.is_empty() {
+        info!("No metrics in the selected range, skipping charts");
+        return Ok(());
+    }
+
+    let metrics = metrics.as_slice();
 
     if let Err(e) = fs::create_dir_all(Path::new(path)).await {
         error!("Failed to create output directory '{}': {}", path, e);
@@ -23,14 +39,19 @@ pub async fn plot_metrics(
     plot_average_response_time(metrics, &format!("{}/average_response_time.png", path))
         .expect("Failed to plot average response time");
 
+    info!("Plotting response phases...");
+
+    plot_response_phases(metrics, &format!("{}/response_phases.png", path))
+        .expect("Failed to plot response phases");
+
     info!("Plotting cumulative successful requests...");
 
-    plot_cumulative_successful_requests(metrics, expected_status_code, &format!("{}/cumulative_successful_requests.png", path))
+    plot_cumulative_successful_requests(metrics, &format!("{}/cumulative_successful_requests.png", path))
         .expect("Failed to plot successful requests");
 
     info!("Plotting cumulative error rate...");
 
-    plot_cumulative_error_rate(metrics, expected_status_code, &format!("{}/cumulative_error_rate.png", path))
+    plot_cumulative_error_rate(metrics, &format!("{}/cumulative_error_rate.png", path))
         .expect("Failed to plot error rate");
 
     info!("Plotting latency percentiles...");
@@ -43,6 +64,11 @@ pub async fn plot_metrics(
     plot_requests_per_second(metrics, &format!("{}/requests_per_second.png", path))
         .expect("Failed to plot requests per second");
 
+    info!("Plotting bandwidth...");
+
+    plot_bandwidth(metrics, &format!("{}/bandwidth.png", path))
+        .expect("Failed to plot bandwidth");
+
     info!("Plotting cumulative total requests...");
 
     plot_cumulative_total_requests(metrics, &format!("{}/cumulative_total_requests.png", path))
@@ -51,41 +77,24 @@ pub async fn plot_metrics(
     Ok(())
 }
 
-fn plot_average_response_time(metrics: &Vec<Metrics>, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn plot_average_response_time(metrics: &[SecondSummary], path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let root = BitMapBackend::new(path, (1600, 600)).into_drawing_area();
     root.fill(&WHITE)?;
 
-    let first_start = metrics[0].start;
-
-    let mut buckets: BTreeMap<u64, Vec<u128>> = BTreeMap::new();
-
-    for metric in metrics {
-        let elapsed = metric.start.duration_since(first_start).as_secs_f64();
-        let bucket_key = (elapsed * 10.0).floor() as u64; // 100ms granularity
-        buckets.entry(bucket_key)
-            .or_default()
-            .push(metric.response_time.as_micros());
-    }
-
-    let mut data: Vec<(f64, u32)> = buckets.into_iter()
-        .map(|(bucket, times)| {
-            let avg_us = times.iter().sum::<u128>() as f64 / times.len() as f64;
-            let avg_ms = avg_us / 1000.0;
-            (bucket as f64 * 0.1, avg_ms as u32)
-        })
+    let data: Vec<(f64, u32)> = metrics.iter()
+        .map(|s| (s.second as f64, s.service.mean as u32))
         .collect();
 
-    data.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-    let x_max = data.iter().map(|(x, _)| *x).fold(0.0, f64::max);
-    let y_max = data.iter().map(|(_, y)| *y).max().unwrap_or(1000);
+    let x_min = data.first().map(|(x, _)| *x).unwrap_or(0.0);
+    let x_max = data.iter().map(|(x, _)| *x).fold(x_min + 1.0, f64::max);
+    let y_max = data.iter().map(|(_, y)| *y).max().unwrap_or(1000).max(1);
 
     let mut chart = ChartBuilder::on(&root)
         .caption("Average Response Time", ("sans-serif", 30).into_font())
         .margin(10)
         .x_label_area_size(40)
         .y_label_area_size(40)
-        .build_cartesian_2d(0.0..x_max, 0u32..y_max)?;
+        .build_cartesian_2d(x_min..x_max, 0u32..y_max)?;
 
     chart.configure_mesh()
         .x_desc("Elapsed Time (seconds)")
@@ -94,172 +103,223 @@ fn plot_average_response_time(metrics: &Vec<Metrics>, path: &str) -> Result<(),
         .y_labels(10)
         .draw()?;
 
-    chart.draw_series(LineSeries::new(data.into_iter(), &BLUE))?;
+    chart.draw_series(LineSeries::new(data, &BLUE))?;
 
     root.present()?;
     Ok(())
 }
 
-pub fn plot_cumulative_successful_requests(
-    metrics: &Vec<Metrics>,
-    expected_status_code: &u16,
-    path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// Stacks the mean time of every phase per second, so the top edge is the mean response time.
+fn plot_response_phases(metrics: &[SecondSummary], path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let root = BitMapBackend::new(path, (1600, 600)).into_drawing_area();
     root.fill(&WHITE)?;
 
-    let first_start = metrics[0].start;
-    let mut success_buckets: BTreeMap<u64, u32> = BTreeMap::new();
-
-    for metric in metrics {
-        if metric.status_code == *expected_status_code {
-            let elapsed = metric.start.duration_since(first_start).as_secs_f64();
-            let bucket = (elapsed * 10.0).floor() as u64; // 100ms buckets
-            *success_buckets.entry(bucket).or_insert(0) += 1;
-        }
+    type Layer = (&'static str, fn(&SecondSummary) -> f64, RGBColor);
+
+    let layers: [Layer; 5] = [
+        ("DNS", |s| s.phases.dns, GREEN),
+        ("TCP connect", |s| s.phases.connect, CYAN),
+        ("TLS handshake", |s| s.phases.tls, MAGENTA),
+        ("Server wait", |s| s.phases.wait, BLUE),
+        ("Download", |s| s.phases.download, RED),
+    ];
+
+    // Top edge of every layer: the sum of that layer and the ones below it.
+    let mut tops: Vec<Vec<(f64, f64)>> = Vec::new();
+    for (_, value, _) in &layers {
+        let top = metrics
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let below = tops.last().map(|previous| previous[i].1).unwrap_or(0.0);
+                (s.second as f64, below + value(s))
+            })
+            .collect();
+        tops.push(top);
     }
 
-    let max_bucket = *success_buckets.keys().max().unwrap_or(&0);
-    let mut cumulative = 0;
-    let mut data: Vec<(f64, u32)> = Vec::with_capacity((max_bucket + 1) as usize);
-
-    for bucket in 0..=max_bucket {
-        let count = *success_buckets.get(&bucket).unwrap_or(&0);
-        cumulative += count;
-        data.push((bucket as f64 * 0.1, cumulative));
-    }
-
-    let x_max = data.last().map(|(x, _)| *x).unwrap_or(1.0);
-    let y_max = data.last().map(|(_, y)| *y).unwrap_or(1);
+    let x_min = metrics.first().map(|s| s.second as f64).unwrap_or(0.0);
+    let x_max = metrics.last().map(|s| s.second as f64).unwrap_or(0.0).max(x_min + 1.0);
+    let y_max = tops
+        .last()
+        .map(|top| top.iter().map(|(_, y)| *y).fold(1.0, f64::max))
+        .unwrap_or(1.0);
 
     let mut chart = ChartBuilder::on(&root)
-        .caption("Cumulative Successful Requests", ("sans-serif", 30))
+        .caption("Response Phases", ("sans-serif", 30))
         .margin(10)
-        .x_label_area_size(50)
+        .x_label_area_size(40)
         .y_label_area_size(60)
-        .build_cartesian_2d(0.0..x_max, 0u32..y_max)?;
+        .build_cartesian_2d(x_min..x_max, 0.0..y_max)?;
 
     chart
         .configure_mesh()
         .x_desc("Elapsed Time (seconds)")
-        .y_desc("Successful Requests")
+        .y_desc("Mean Time (ms)")
         .x_labels(20)
         .y_labels(10)
         .draw()?;
 
-    chart.draw_series(LineSeries::new(data.into_iter(), &BLUE))?;
+    // Drawn from the top down, so every layer covers the lower part of the one above.
+    for ((label, _, color), top) in layers.iter().zip(tops).rev() {
+        let color = *color;
+        chart
+            .draw_series(AreaSeries::new(top, 0.0, color.filled()).border_style(BLACK))?
+            .label(*label)
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
 
     root.present()?;
     Ok(())
 }
 
-pub fn plot_cumulative_error_rate(
-    metrics: &Vec<Metrics>,
-    expected_status_code: &u16,
+/// Running total of `count` over the seconds in `metrics`.
+fn cumulative(metrics: &[SecondSummary], count: impl Fn(&SecondSummary) -> u64) -> Vec<(f64, u64)> {
+    let mut total = 0;
+
+    metrics
+        .iter()
+        .map(|s| {
+            total += count(s);
+            (s.second as f64, total)
+        })
+        .collect()
+}
+
+/// Plots a running total, plus one labelled line per entry in `breakdown` if it is not empty.
+fn plot_cumulative(
+    data: Vec<(f64, u64)>,
+    breakdown: Vec<(String, Vec<(f64, u64)>)>,
+    caption: &str,
+    y_desc: &str,
+    color: &RGBColor,
     path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let root = BitMapBackend::new(path, (1600, 600)).into_drawing_area();
     root.fill(&WHITE)?;
 
-    let first_start = metrics[0].start;
-    let mut error_buckets: BTreeMap<u64, u32> = BTreeMap::new();
-
-    for metric in metrics {
-        if metric.status_code != *expected_status_code {
-            let elapsed = metric.start.duration_since(first_start).as_secs_f64();
-            let bucket = (elapsed * 10.0).floor() as u64; // 100ms buckets
-            *error_buckets.entry(bucket).or_insert(0) += 1;
-        }
-    }
-
-    let max_bucket = *error_buckets.keys().max().unwrap_or(&0);
-    let mut cumulative = 0;
-    let mut data: Vec<(f64, u32)> = Vec::with_capacity((max_bucket + 1) as usize);
-
-    for bucket in 0..=max_bucket {
-        let count = *error_buckets.get(&bucket).unwrap_or(&0);
-        cumulative += count;
-        data.push((bucket as f64 * 0.1, cumulative));
-    }
-
-    let x_max = data.last().map(|(x, _)| *x).unwrap_or(1.0);
-    let y_max = data.last().map(|(_, y)| *y).unwrap_or(1);
+    let x_min = data.first().map(|(x, _)| *x).unwrap_or(0.0);
+    let x_max = data.last().map(|(x, _)| *x).unwrap_or(1.0).max(x_min + 1.0);
+    let y_max = data.last().map(|(_, y)| *y).unwrap_or(1).max(1);
 
     let mut chart = ChartBuilder::on(&root)
-        .caption("Cumulative Errors Over Time", ("sans-serif", 30))
+        .caption(caption, ("sans-serif", 30))
         .margin(10)
         .x_label_area_size(50)
         .y_label_area_size(60)
-        .build_cartesian_2d(0.0..x_max, 0u32..y_max)?;
+        .build_cartesian_2d(x_min..x_max, 0u64..y_max)?;
 
     chart
         .configure_mesh()
         .x_desc("Elapsed Time (seconds)")
-        .y_desc("Cumulative Errors")
+        .y_desc(y_desc)
         .x_labels(20)
         .y_labels(10)
         .draw()?;
 
-    chart.draw_series(LineSeries::new(data.into_iter(), &RED))?;
+    if breakdown.is_empty() {
+        chart.draw_series(LineSeries::new(data, color))?;
+    } else {
+        let color = *color;
+        chart
+            .draw_series(LineSeries::new(data, color))?
+            .label(y_desc)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+
+        for (i, (label, series)) in breakdown.into_iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            chart
+                .draw_series(LineSeries::new(series, color))?
+                .label(label)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+    }
 
     root.present()?;
     Ok(())
 }
 
-pub fn plot_latency_percentiles(
-    metrics: &Vec<Metrics>,
-    base_path: &str,
+pub fn plot_cumulative_successful_requests(
+    metrics: &[SecondSummary],
+    path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    fn percentile(sorted: &[u128], pct: f64) -> u128 {
-        let index = ((pct / 100.0) * sorted.len() as f64).round() as usize;
-        *sorted.get(index.min(sorted.len() - 1)).unwrap_or(&0)
-    }
+    plot_cumulative(
+        cumulative(metrics, |s| s.successful_requests),
+        Vec::new(),
+        "Cumulative Successful Requests",
+        "Successful Requests",
+        &BLUE,
+        path,
+    )
+}
 
-    let first_start = metrics[0].start;
+pub fn plot_cumulative_error_rate(
+    metrics: &[SecondSummary],
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let names: BTreeSet<&'static str> = metrics
+        .iter()
+        .flat_map(|s| s.assertion_failures.keys().copied())
+        .collect();
 
-    let mut grouped: BTreeMap<u64, Vec<u128>> = BTreeMap::new();
-    for m in metrics {
-        let second = m.start.duration_since(first_start).as_secs();
-        grouped.entry(second).or_default().push(m.response_time.as_micros());
-    }
+    let breakdown = names
+        .into_iter()
+        .map(|name| {
+            let series = cumulative(metrics, |s| s.assertion_failures.get(name).copied().unwrap_or(0));
+            (format!("assert {}", name), series)
+        })
+        .collect();
 
-    let mut p50s = vec![];
-    let mut p90s = vec![];
-    let mut p99s = vec![];
-    let mut seconds = vec![];
-
-    for (sec, mut times) in grouped {
-        times.sort_unstable();
-        p50s.push(percentile(&times, 50.0) / 1000); // ms
-        p90s.push(percentile(&times, 90.0) / 1000);
-        p99s.push(percentile(&times, 99.0) / 1000);
-        seconds.push(sec);
-    }
+    plot_cumulative(
+        cumulative(metrics, SecondSummary::errors),
+        breakdown,
+        "Cumulative Errors Over Time",
+        "Cumulative Errors",
+        &RED,
+        path,
+    )
+}
 
-    let y_max_p50 = *p50s.iter().max().unwrap_or(&100);
-    let y_max_p90 = *p90s.iter().max().unwrap_or(&100);
-    let y_max_p99 = *p99s.iter().max().unwrap_or(&100);
+pub fn plot_latency_percentiles(
+    metrics: &[SecondSummary],
+    base_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let seconds: Vec<u64> = metrics.iter().map(|s| s.second).collect();
 
     fn draw_chart(
-        seconds: &Vec<u64>,
-        values: &Vec<u128>,
+        seconds: &[u64],
+        values: &[f64],
+        corrected_values: &[f64],
         title: &str,
         color: RGBColor,
         file_path: &str,
-        y_max: u128,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let root = BitMapBackend::new(file_path, (1600, 600)).into_drawing_area();
         root.fill(&WHITE)?;
 
         let x_min = *seconds.first().unwrap_or(&0);
-        let x_max = *seconds.last().unwrap_or(&0);
+        let x_max = (*seconds.last().unwrap_or(&0)).max(x_min + 1);
+        // Corrected latency is never below service time, so it bounds the y-axis.
+        let y_max = corrected_values.iter().cloned().fold(1.0, f64::max);
 
         let mut chart = ChartBuilder::on(&root)
             .caption(title, ("sans-serif", 30))
             .margin(10)
             .x_label_area_size(30)
             .y_label_area_size(50)
-            .build_cartesian_2d(x_min..x_max, 0u128..y_max)?;
+            .build_cartesian_2d(x_min..x_max, 0.0..y_max)?;
 
         chart
             .configure_mesh()
@@ -267,70 +327,80 @@ pub fn plot_latency_percentiles(
             .y_desc("Latency (ms)")
             .draw()?;
 
-        let points: Vec<(u64, u128)> = seconds.iter().cloned().zip(values.iter().cloned()).collect();
-        chart.draw_series(LineSeries::new(points, &color))?;
+        let points: Vec<(u64, f64)> = seconds.iter().cloned().zip(values.iter().cloned()).collect();
+        chart
+            .draw_series(LineSeries::new(points, &color))?
+            .label("Service time")
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+
+        let corrected_points: Vec<(u64, f64)> = seconds.iter().cloned().zip(corrected_values.iter().cloned()).collect();
+        chart
+            .draw_series(LineSeries::new(corrected_points, &MAGENTA))?
+            .label("Corrected (intended start)")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], MAGENTA));
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
 
         root.present()?;
         Ok(())
     }
 
+    let series = |pick: fn(&SecondSummary) -> (f64, f64)| -> (Vec<f64>, Vec<f64>) {
+        metrics.iter().map(pick).unzip()
+    };
+
+    let (p50s, corrected_p50s) = series(|s| (s.service.p50, s.corrected.p50));
+    let (p90s, corrected_p90s) = series(|s| (s.service.p90, s.corrected.p90));
+    let (p99s, corrected_p99s) = series(|s| (s.service.p99, s.corrected.p99));
+
     draw_chart(
         &seconds,
         &p50s,
+        &corrected_p50s,
         "Latency P50",
         BLUE,
         &format!("{}_P50.png", base_path),
-        y_max_p50,
     )?;
 
     draw_chart(
         &seconds,
         &p90s,
+        &corrected_p90s,
         "Latency P90",
         GREEN,
         &format!("{}_P90.png", base_path),
-        y_max_p90,
     )?;
 
     draw_chart(
         &seconds,
         &p99s,
+        &corrected_p99s,
         "Latency P99",
         RED,
         &format!("{}_P99.png", base_path),
-        y_max_p99,
     )?;
 
     Ok(())
 }
 
-pub fn plot_requests_per_second(metrics: &Vec<Metrics>, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn plot_requests_per_second(metrics: &[SecondSummary], path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let root = BitMapBackend::new(path, (1600, 600)).into_drawing_area();
     root.fill(&WHITE)?;
 
-    let first_start = metrics[0].start;
-
-    let elapsed_secs: Vec<u64> = metrics.iter()
-        .map(|m| m.start.duration_since(first_start).as_secs())
-        .collect();
-
-    let max_sec = *elapsed_secs.iter().max().unwrap();
-
-    let mut counts = vec![0u32; (max_sec + 1) as usize];
-    for &sec in &elapsed_secs {
-        counts[sec as usize] += 1;
-    }
-
-    let x_range = 0u32..(max_sec as u32 + 1);
-    let y_max = *counts.iter().max().unwrap_or(&1);
-    let y_range = 0u32..(y_max + 1);
+    let x_min = metrics.first().map(|s| s.second).unwrap_or(0);
+    let x_max = metrics.last().map(|s| s.second).unwrap_or(0) + 1;
+    let y_max = metrics.iter().map(|s| s.requests).max().unwrap_or(1);
 
     let mut chart = ChartBuilder::on(&root)
         .caption("Requests per Second", ("sans-serif", 30).into_font())
         .margin(10)
         .x_label_area_size(40)
         .y_label_area_size(40)
-        .build_cartesian_2d(x_range.clone(), y_range)?;
+        .build_cartesian_2d(x_min..x_max, 0u64..(y_max + 1))?;
 
     chart.configure_mesh()
         .x_desc("Elapsed Time (seconds)")
@@ -338,7 +408,7 @@ pub fn plot_requests_per_second(metrics: &Vec<Metrics>, path: &str) -> Result<()
         .draw()?;
 
     chart.draw_series(LineSeries::new(
-        counts.iter().enumerate().map(|(sec, &count)| (sec as u32, count)),
+        metrics.iter().map(|s| (s.second, s.requests)),
         &BLUE,
     ))?;
 
@@ -346,52 +416,60 @@ pub fn plot_requests_per_second(metrics: &Vec<Metrics>, path: &str) -> Result<()
     Ok(())
 }
 
-pub fn plot_cumulative_total_requests(
-    metrics: &Vec<Metrics>,
-    path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+pub fn plot_bandwidth(metrics: &[SecondSummary], path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let root = BitMapBackend::new(path, (1600, 600)).into_drawing_area();
     root.fill(&WHITE)?;
 
-    let first_start = metrics[0].start;
-    let mut total_buckets: BTreeMap<u64, u32> = BTreeMap::new();
-
-    for metric in metrics {
-        let elapsed = metric.start.duration_since(first_start).as_secs_f64();
-        let bucket = (elapsed * 10.0).floor() as u64; // 100ms buckets
-        *total_buckets.entry(bucket).or_insert(0) += 1;
-    }
-
-    let max_bucket = *total_buckets.keys().max().unwrap_or(&0);
-    let mut cumulative = 0;
-    let mut data: Vec<(f64, u32)> = Vec::with_capacity((max_bucket + 1) as usize);
+    let mb = |bytes: u64| bytes as f64 / 1_000_000.0;
+    let received: Vec<(u64, f64)> = metrics.iter().map(|s| (s.second, mb(s.bytes_received))).collect();
+    let sent: Vec<(u64, f64)> = metrics.iter().map(|s| (s.second, mb(s.bytes_sent))).collect();
 
-    for bucket in 0..=max_bucket {
-        let count = *total_buckets.get(&bucket).unwrap_or(&0);
-        cumulative += count;
-        data.push((bucket as f64 * 0.1, cumulative));
-    }
-
-    let x_max = data.last().map(|(x, _)| *x).unwrap_or(1.0);
-    let y_max = data.last().map(|(_, y)| *y).unwrap_or(1);
+    let x_min = metrics.first().map(|s| s.second).unwrap_or(0);
+    let x_max = metrics.last().map(|s| s.second).unwrap_or(0) + 1;
+    let y_max = received.iter().chain(&sent).map(|(_, y)| *y).fold(0.0, f64::max).max(0.1) * 1.1;
 
     let mut chart = ChartBuilder::on(&root)
-        .caption("Cumulative Total Requests", ("sans-serif", 30))
+        .caption("Bandwidth", ("sans-serif", 30).into_font())
         .margin(10)
-        .x_label_area_size(50)
+        .x_label_area_size(40)
         .y_label_area_size(60)
-        .build_cartesian_2d(0.0..x_max, 0u32..y_max)?;
+        .build_cartesian_2d(x_min..x_max, 0.0..y_max)?;
 
-    chart
-        .configure_mesh()
+    chart.configure_mesh()
         .x_desc("Elapsed Time (seconds)")
-        .y_desc("Cumulative Total Requests")
-        .x_labels(20)
-        .y_labels(10)
+        .y_desc("Body Throughput (MB/s)")
         .draw()?;
 
-    chart.draw_series(LineSeries::new(data.into_iter(), &BLACK))?;
+    chart
+        .draw_series(LineSeries::new(received, &BLUE))?
+        .label("Received")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+
+    chart
+        .draw_series(LineSeries::new(sent, &GREEN))?
+        .label("Sent")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], GREEN));
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
 
     root.present()?;
     Ok(())
 }
+
+pub fn plot_cumulative_total_requests(
+    metrics: &[SecondSummary],
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    plot_cumulative(
+        cumulative(metrics, |s| s.requests),
+        Vec::new(),
+        "Cumulative Total Requests",
+        "Cumulative Total Requests",
+        &BLACK,
+        path,
+    )
+}
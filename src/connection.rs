@@ -0,0 +1,113 @@
+use std::{cell::Cell, fmt::{self, Write}, future::Future, net::SocketAddr, time::Duration};
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tokio::time::Instant;
+use tracing::{field::{Field, Visit}, Event, Level, Subscriber};
+use tracing_subscriber::{filter::Targets, layer::Context, Layer};
+
+/// Time the request running on a task spent opening connections. All zero when a pooled connection was reused.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectionTimes {
+    /// DNS lookup, also zero when the host is an IP address
+    pub dns: Duration,
+    /// TCP connect, including attempts to addresses that failed
+    pub connect: Duration,
+    /// TLS handshake
+    pub tls: Duration,
+    /// Start of the first attempt of the TCP connect in progress
+    connecting: Option<Instant>,
+    /// End of the TCP connect, where the TLS handshake starts
+    connected: Option<Instant>,
+}
+
+tokio::task_local! {
+    static CONNECTION_TIMES: Cell<ConnectionTimes>;
+}
+
+fn update(f: impl FnOnce(&mut ConnectionTimes)) {
+    // Connections opened in the background are not attributed to a request.
+    let _ = CONNECTION_TIMES.try_with(|cell| {
+        let mut times = cell.get();
+        f(&mut times);
+        cell.set(times);
+    });
+}
+
+/// System resolver that reports how long each lookup took to the request that caused it.
+pub struct TimedResolver;
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let start = Instant::now();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+
+            update(|times| times.dns += start.elapsed());
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Times TCP connects and TLS handshakes from the events hyper emits while it opens a connection:
+/// `connecting to` and `connected to` around the TCP connect, then `client handshake` once the TLS
+/// handshake is done and HTTP starts. reqwest has no hook to wrap its connector, so this is the only
+/// way to see the steps. The events come from the request's own task, like the lookups of `TimedResolver`.
+pub struct ConnectionEvents;
+
+impl ConnectionEvents {
+    /// The events this layer needs, so it does not enable any other debug or trace event.
+    pub fn filter() -> Targets {
+        Targets::new()
+            .with_target("hyper::client::connect::http", Level::DEBUG)
+            .with_target("hyper::client::conn", Level::TRACE)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ConnectionEvents {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let now = Instant::now();
+        let mut message = Message(String::new());
+        event.record(&mut message);
+
+        if message.0.starts_with("connecting to") {
+            update(|times| {
+                times.connecting.get_or_insert(now);
+            });
+        } else if message.0.starts_with("connected to") {
+            update(|times| {
+                if let Some(start) = times.connecting.take() {
+                    times.connect += now - start;
+                    times.connected = Some(now);
+                }
+            });
+        } else if message.0.starts_with("client handshake") {
+            update(|times| {
+                if let Some(connected) = times.connected.take() {
+                    times.tls += now - connected;
+                }
+            });
+        }
+    }
+}
+
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        }
+    }
+}
+
+/// Runs `future` and returns its output with the time it spent opening connections.
+pub async fn timed<F: Future>(future: F) -> (F::Output, ConnectionTimes) {
+    CONNECTION_TIMES
+        .scope(Cell::new(ConnectionTimes::default()), async {
+            let output = future.await;
+            (output, CONNECTION_TIMES.with(Cell::get))
+        })
+        .await
+}
@@ -0,0 +1,87 @@
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A small JSONPath subset: `$.data.items[0].id`, `$['key with spaces']`.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl std::str::FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid JSON path '{}': {}", s, reason);
+
+        let mut rest = s
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| invalid("must start with '$'"))?;
+        let mut segments = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                if end == 0 {
+                    return Err(invalid("empty key"));
+                }
+                segments.push(Segment::Key(after_dot[..end].to_string()));
+                rest = &after_dot[end..];
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket.find(']').ok_or_else(|| invalid("missing ']'"))?;
+                let inner = after_bracket[..end].trim();
+
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+
+                match quoted {
+                    Some(key) => segments.push(Segment::Key(key.to_string())),
+                    None => {
+                        let index = inner.parse().map_err(|_| invalid("index must be a number or a quoted key"))?;
+                        segments.push(Segment::Index(index));
+                    }
+                }
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(invalid("expected '.' or '['"));
+            }
+        }
+
+        Ok(Self {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl JsonPath {
+    pub fn find<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments.iter().try_fold(value, |current, segment| match segment {
+            Segment::Key(key) => current.get(key),
+            Segment::Index(index) => current.get(index),
+        })
+    }
+
+    /// Finds the value and turns it into a string suitable for templates.
+    /// Strings are used as-is, everything else as its JSON text.
+    pub fn extract(&self, value: &Value) -> Option<String> {
+        self.find(value).map(|found| match found {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
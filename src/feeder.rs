@@ -0,0 +1,127 @@
+use std::{path::Path, sync::atomic::{AtomicBool, AtomicUsize, Ordering}};
+
+use rand::Rng;
+use serde_json::Value;
+use tracing::info;
+
+use crate::{args::{DataExhausted, DataStrategy, TesterArgs}, template::Variables};
+
+type Row = Vec<(String, String)>;
+
+/// Rows from `--data-file`, handed out to workers as template variables.
+pub struct Feeder {
+    rows: Vec<Row>,
+    strategy: DataStrategy,
+    on_exhausted: DataExhausted,
+    cursor: AtomicUsize,
+    exhausted: AtomicBool,
+}
+
+impl Feeder {
+    pub fn from_args(args: &TesterArgs) -> Result<Option<Self>, String> {
+        let Some(path) = &args.data_file else {
+            return Ok(None);
+        };
+
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let rows = match extension.as_str() {
+            "jsonl" | "ndjson" => read_json_lines(path)?,
+            _ => read_csv(path)?,
+        };
+
+        if rows.is_empty() {
+            return Err(format!("Data file '{}' does not contain any rows", path));
+        }
+
+        Ok(Some(Self {
+            rows,
+            strategy: args.data_strategy,
+            on_exhausted: args.data_exhausted,
+            cursor: AtomicUsize::new(0),
+            exhausted: AtomicBool::new(false),
+        }))
+    }
+
+    /// Whether a row is taken once per worker instead of once per iteration.
+    pub fn per_worker(&self) -> bool {
+        self.strategy == DataStrategy::Unique
+    }
+
+    /// Copies the next row into `vars`. Returns false once the rows ran out and the test should stop.
+    pub fn fill(&self, vars: &mut Variables) -> bool {
+        let index = match self.strategy {
+            DataStrategy::Random => rand::thread_rng().gen_range(0..self.rows.len()),
+            DataStrategy::Sequential | DataStrategy::Unique => {
+                let next = self.cursor.fetch_add(1, Ordering::Relaxed);
+
+                match self.on_exhausted {
+                    DataExhausted::Recycle => next % self.rows.len(),
+                    DataExhausted::Stop if next < self.rows.len() => next,
+                    DataExhausted::Stop => {
+                        if !self.exhausted.swap(true, Ordering::Relaxed) {
+                            info!("All {} rows of the data file were used, stopping the test", self.rows.len());
+                        }
+                        return false;
+                    }
+                }
+            }
+        };
+
+        vars.extend(self.rows[index].iter().cloned());
+        true
+    }
+
+    /// Whether any row sets the variable `name`.
+    pub fn has_column(&self, name: &str) -> bool {
+        self.rows.iter().any(|row| row.iter().any(|(column, _)| column == name))
+    }
+
+    /// The first row, without advancing the feeder.
+    pub fn first_row(&self) -> &[(String, String)] {
+        &self.rows[0]
+    }
+}
+
+fn read_csv(path: &str) -> Result<Vec<Row>, String> {
+    let invalid = |e: csv::Error| format!("Invalid CSV in '{}': {}", path, e);
+
+    let mut reader = csv::Reader::from_path(path).map_err(|e| format!("Failed to read data file '{}': {}", path, e))?;
+    let headers = reader.headers().map_err(invalid)?.clone();
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(invalid)?;
+            Ok(headers.iter().map(String::from).zip(record.iter().map(String::from)).collect())
+        })
+        .collect()
+}
+
+fn read_json_lines(path: &str) -> Result<Vec<Row>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Failed to read data file '{}': {}", path, e))?;
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let invalid = || format!("Line {} of '{}' is not a JSON object", number + 1, path);
+
+            match serde_json::from_str::<Value>(line).map_err(|_| invalid())? {
+                Value::Object(fields) => Ok(fields
+                    .into_iter()
+                    .map(|(key, value)| match value {
+                        Value::String(s) => (key, s),
+                        other => (key, other.to_string()),
+                    })
+                    .collect()),
+                _ => Err(invalid()),
+            }
+        })
+        .collect()
+}
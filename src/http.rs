@@ -1,24 +1,76 @@
 extern crate reqwest;
 extern crate async_trait;
 
-use std::time::Duration;
+use std::{collections::HashSet, path::Path, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::Duration};
 
-use reqwest::{Client, Proxy, Request};
-use tokio::{sync::{broadcast, mpsc}, time::{interval, sleep, Instant}};
+use rand::distributions::{Distribution, WeightedIndex};
+use bytes::Bytes;
+use reqwest::{header::{HeaderMap, HeaderValue, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE}, multipart, tls, Body, Certificate, Client, ClientBuilder, Identity, Proxy, Request, RequestBuilder, Response};
+use tokio::{sync::{broadcast, oneshot::{self, error::TryRecvError}, watch, Semaphore}, time::{interval, sleep, sleep_until, Instant}};
 use tracing::error;
 
-use crate::{args::{HttpMethod, TesterArgs}, metrics::Metrics};
+use crate::{args::{BodyMode, HttpMethod, TesterArgs, TlsVersion}, assertions::Assertion, connection::{self, TimedResolver}, extract::JsonPath, feeder::Feeder, load::LoadShape, metrics::{Metrics, MetricsRecorder, MetricsSink, Outcome, Phases, ResponseInfo}, scenario::{RequestDefinition, Scenario}, template::{self, Template, Variables}};
 
 pub fn setup_request_sender(
     args: &TesterArgs,
+    scenario: &Scenario,
     shutdown_tx: &broadcast::Sender<u16>,
-    metrics_tx: &mpsc::UnboundedSender<Metrics>,
-) -> Option<tokio::task::JoinHandle<()>> {
+    metrics_sink: &MetricsSink,
+    missed_slots: &Arc<AtomicU64>,
+) -> Option<tokio::task::JoinHandle<bool>> {
     let shutdown_tx = shutdown_tx.clone();
-    let metrics_tx = metrics_tx.clone();
+    let metrics_sink = metrics_sink.clone();
+    let missed_slots = missed_slots.clone();
 
     let mut client_builder = Client::builder()
-        .timeout(std::time::Duration::from_secs(10));
+        .timeout(args.timeout)
+        .tcp_nodelay(!args.no_tcp_nodelay)
+        .tcp_keepalive(args.tcp_keepalive)
+        .dns_resolver(Arc::new(TimedResolver));
+
+    if let Some(connect_timeout) = args.connect_timeout {
+        client_builder = client_builder.connect_timeout(connect_timeout);
+    }
+
+    if let Some(idle_timeout) = args.pool_idle_timeout {
+        client_builder = client_builder.pool_idle_timeout(idle_timeout);
+    }
+
+    if let Some(max_idle) = args.pool_max_idle_per_host {
+        client_builder = client_builder.pool_max_idle_per_host(max_idle);
+    }
+
+    if args.http3 {
+        error!("HTTP/3 is not supported yet: reqwest only offers it behind an unstable build flag");
+        let _ = shutdown_tx.send(1);
+        return None;
+    }
+
+    if args.http1_only {
+        client_builder = client_builder.http1_only();
+    } else if args.http2 {
+        client_builder = client_builder.http2_prior_knowledge();
+    }
+
+    // reqwest overrides the host for every port and connects to the port of the URL, so the parsed port is unused.
+    for (host, addr) in &args.resolve {
+        client_builder = client_builder.resolve(host, *addr);
+    }
+
+    client_builder = match configure_tls(client_builder, args) {
+        Ok(client_builder) => client_builder,
+        Err(e) => {
+            error!("{}", e);
+            let _ = shutdown_tx.send(1);
+            return None;
+        }
+    };
+
+    if args.no_keepalive {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        client_builder = client_builder.pool_max_idle_per_host(0).default_headers(headers);
+    }
 
     if let Some(ref proxy_url) = args.proxy_url {
         match Proxy::all(proxy_url) {
@@ -42,25 +94,61 @@ pub fn setup_request_sender(
         }
     };
 
-    let mut request_builder = match args.method {
-        HttpMethod::Get => client.get(&args.url),
-        HttpMethod::Post => client.post(&args.url),
-        HttpMethod::Patch => client.patch(&args.url),
-        HttpMethod::Put => client.put(&args.url),
-        HttpMethod::Delete => client.delete(&args.url),
+    let feeder = match Feeder::from_args(args) {
+        Ok(feeder) => feeder,
+        Err(e) => {
+            error!("{}", e);
+            let _ = shutdown_tx.send(1);
+            return None;
+        }
     };
 
-    for (key, value) in &args.headers {
-        request_builder = request_builder.header(key, value);
+    // Variables every request can read. In a flow, extracted ones only become available to later steps.
+    let mut known: HashSet<String> = template::new_variables(0).into_keys().collect();
+    if !scenario.is_flow() {
+        known.extend(scenario.definitions().iter().flat_map(|definition| definition.extract.keys().cloned()));
     }
 
-    let request = match request_builder.body(args.data.clone()).build() {
-        Ok(req) => req,
-        Err(e) => {
-            error!("Failed to build request: {}", e);
+    let mut endpoints = Vec::with_capacity(scenario.definitions().len());
+
+    for definition in scenario.definitions() {
+        let endpoint = match Endpoint::from_definition(definition, args) {
+            Ok(endpoint) => endpoint,
+            Err(e) => {
+                error!("Invalid request '{}': {}", definition.name, e);
+                let _ = shutdown_tx.send(1);
+                return None;
+            }
+        };
+
+        let unknown = endpoint.variables().find(|name| {
+            !known.contains(*name) && !feeder.as_ref().is_some_and(|feeder| feeder.has_column(name))
+        });
+        if let Some(name) = unknown {
+            error!(
+                "Invalid request '{}': '{{{{{}}}}}' is not a generator, a data file column or a variable extracted by an earlier step \
+                 (write '\\{{{{' for a literal '{{{{')",
+                definition.name, name
+            );
             let _ = shutdown_tx.send(1);
             return None;
         }
+
+        known.extend(definition.extract.keys().cloned());
+        endpoints.push(endpoint);
+    }
+
+    let weights = if scenario.is_flow() {
+        None
+    } else {
+        match WeightedIndex::new(scenario.requests.iter().map(|r| r.weight)) {
+            Ok(weights) => Some(weights),
+            Err(e) => {
+                error!("Invalid request weights: {}", e);
+                let _ = shutdown_tx.send(1);
+                return None;
+            }
+        }
     };
 
     let args_clone = args.clone();
@@ -68,83 +156,848 @@ pub fn setup_request_sender(
     Some(create_sender_task(
         args_clone,
         shutdown_tx,
-        metrics_tx,
+        metrics_sink,
+        missed_slots,
         client,
-        request,
+        Arc::new(Endpoints { list: endpoints, weights, feeder }),
     ))
 }
 
+enum MultipartValue {
+    Text(Template),
+    File { file_name: String, mime: String, content: Bytes },
+}
+
+/// The body of an endpoint, rebuilt for every request.
+enum BodyTemplate {
+    Text(Template),
+    Binary(Bytes),
+    Form(Vec<(String, Template)>),
+    Multipart(Vec<(String, MultipartValue)>),
+}
+
+fn read_body_file(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read body file '{}': {}", path, e))
+}
+
+impl BodyTemplate {
+    fn from_definition(definition: &RequestDefinition) -> Result<Self, String> {
+        if !definition.multipart.is_empty() {
+            let fields = definition.multipart
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value.strip_prefix('@') {
+                        Some(path) => MultipartValue::File {
+                            file_name: Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                            mime: mime_guess::from_path(path).first_or_octet_stream().to_string(),
+                            content: read_body_file(path)?.into(),
+                        },
+                        None => MultipartValue::Text(Template::parse(value)?),
+                    };
+                    Ok((name.clone(), value))
+                })
+                .collect::<Result<_, String>>()?;
+
+            return Ok(BodyTemplate::Multipart(fields));
+        }
+
+        if !definition.form.is_empty() {
+            let fields = definition.form
+                .iter()
+                .map(|(name, value)| Template::parse(value).map(|value| (name.clone(), value)))
+                .collect::<Result<_, _>>()?;
+
+            return Ok(BodyTemplate::Form(fields));
+        }
+
+        match (definition.body.strip_prefix('@'), definition.binary) {
+            (Some(path), true) => Ok(BodyTemplate::Binary(read_body_file(path)?.into())),
+            (None, true) => Ok(BodyTemplate::Binary(Bytes::from(definition.body.clone()))),
+            (Some(path), false) => {
+                let text = String::from_utf8(read_body_file(path)?)
+                    .map_err(|_| format!("Body file '{}' is not UTF-8 text, use --data-binary instead", path))?;
+                Ok(BodyTemplate::Text(Template::parse(&text)?))
+            }
+            (None, false) => Ok(BodyTemplate::Text(Template::parse(&definition.body)?)),
+        }
+    }
+
+    /// Content-Type for text and binary bodies, guessed from the file extension or the content.
+    /// Forms and multipart bodies get theirs from reqwest.
+    fn default_content_type(definition: &RequestDefinition) -> Option<String> {
+        if definition.body.is_empty() || !definition.form.is_empty() || !definition.multipart.is_empty() {
+            return None;
+        }
+
+        if let Some(path) = definition.body.strip_prefix('@') {
+            let fallback = if definition.binary { "application/octet-stream" } else { "text/plain" };
+            return Some(mime_guess::from_path(path).first_raw().unwrap_or(fallback).to_string());
+        }
+
+        let content_type = match definition.body.trim_start().chars().next() {
+            _ if definition.binary => "application/octet-stream",
+            Some('{') | Some('[') => "application/json",
+            _ => "text/plain; charset=utf-8",
+        };
+
+        Some(content_type.to_string())
+    }
+
+    fn templates(&self) -> Vec<&Template> {
+        match self {
+            BodyTemplate::Text(template) => vec![template],
+            BodyTemplate::Binary(_) => Vec::new(),
+            BodyTemplate::Form(fields) => fields.iter().map(|(_, value)| value).collect(),
+            BodyTemplate::Multipart(fields) => fields
+                .iter()
+                .filter_map(|(_, value)| match value {
+                    MultipartValue::Text(template) => Some(template),
+                    MultipartValue::File { .. } => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn apply(&self, request_builder: RequestBuilder, vars: &Variables) -> Result<RequestBuilder, Outcome> {
+        let render = |template: &Template| template.render(vars).map_err(|_| Outcome::Template);
+
+        match self {
+            BodyTemplate::Text(template) => {
+                let body = render(template)?;
+                // Methods like HEAD and TRACE must not carry a body, so an empty one is left out entirely.
+                Ok(if body.is_empty() { request_builder } else { request_builder.body(body) })
+            }
+            BodyTemplate::Binary(bytes) => Ok(request_builder.body(bytes.clone())),
+            BodyTemplate::Form(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| render(value).map(|value| (name.as_str(), value)))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(request_builder.form(&fields))
+            }
+            BodyTemplate::Multipart(fields) => {
+                let mut form = multipart::Form::new();
+
+                for (name, value) in fields {
+                    form = match value {
+                        MultipartValue::Text(template) => form.text(name.clone(), render(template)?),
+                        MultipartValue::File { file_name, mime, content } => {
+                            let part = multipart::Part::stream_with_length(Body::from(content.clone()), content.len() as u64)
+                                .file_name(file_name.clone())
+                                .mime_str(mime)
+                                .map_err(|e| classify_error(&e))?;
+                            form.part(name.clone(), part)
+                        }
+                    };
+                }
+
+                Ok(request_builder.multipart(form))
+            }
+        }
+    }
+}
+
+/// A request whose URL, header values and body are rendered from the virtual user's variables before every send.
+pub struct Endpoint {
+    method: HttpMethod,
+    url: Template,
+    headers: Vec<(String, Template)>,
+    body: BodyTemplate,
+    content_type: Option<String>,
+    expected_status: u16,
+    extract: Vec<(String, JsonPath)>,
+    assertions: Vec<Assertion>,
+    body_mode: BodyMode,
+}
+
+impl Endpoint {
+    fn from_definition(definition: &RequestDefinition, args: &TesterArgs) -> Result<Self, String> {
+        let headers = definition.headers
+            .iter()
+            .map(|(key, value)| Template::parse(value).map(|value| (key.clone(), value)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let has_content_type = headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+        let content_type = if has_content_type { None } else { BodyTemplate::default_content_type(definition) };
+
+        let extract = definition.extract
+            .iter()
+            .map(|(name, path)| path.parse().map(|path| (name.clone(), path)))
+            .collect::<Result<_, _>>()?;
+
+        let mut assertions = args.assertions.clone();
+        for assertion in &definition.assert {
+            assertions.push(assertion.parse()?);
+        }
+
+        Ok(Self {
+            method: definition.method.clone(),
+            url: Template::parse(&definition.url)?,
+            headers,
+            content_type,
+            body: BodyTemplate::from_definition(definition)?,
+            expected_status: definition.expected_status.unwrap_or(args.expected_status_code),
+            extract,
+            assertions,
+            body_mode: args.body_mode,
+        })
+    }
+
+    /// Variables read by the URL, header values and body.
+    fn variables(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.url)
+            .chain(self.headers.iter().map(|(_, value)| value))
+            .chain(self.body.templates())
+            .flat_map(Template::variables)
+    }
+
+    fn build(&self, client: &Client, vars: &Variables) -> Result<Request, Outcome> {
+        let url = self.url.render(vars).map_err(|_| Outcome::Template)?;
+
+        let mut request_builder = client.request(self.method.0.clone(), &url);
+
+        for (key, value) in &self.headers {
+            request_builder = request_builder.header(key, value.render(vars).map_err(|_| Outcome::Template)?);
+        }
+
+        if let Some(content_type) = &self.content_type {
+            request_builder = request_builder.header(CONTENT_TYPE, content_type);
+        }
+
+        self.body
+            .apply(request_builder, vars)?
+            .build()
+            .map_err(|e| classify_error(&e))
+    }
+
+    /// Status assertions replace the single expected status when present.
+    fn accepts_status(&self, status: u16) -> bool {
+        let mut status_checks = self.assertions.iter().filter(|a| a.is_status()).peekable();
+
+        if status_checks.peek().is_none() {
+            status == self.expected_status
+        } else {
+            status_checks.all(|a| a.check_head(status, &Default::default()))
+        }
+    }
+
+    /// Reads the response body as `--body` asks, recording it in `phases`, and runs the assertions and
+    /// extraction rules. The body is always kept in memory when one of them needs it.
+    async fn evaluate(&self, resp: Response, vars: &mut Variables, phases: &mut Phases) -> Outcome {
+        let status = resp.status().as_u16();
+
+        let failed_head = match self.assertions.iter().find(|a| !a.check_head(status, resp.headers())) {
+            Some(failed) => Some(Outcome::Assertion(failed.name())),
+            None => (!self.accepts_status(status)).then_some(Outcome::Status(status)),
+        };
+
+        let needs_body = failed_head.is_none()
+            && (!self.extract.is_empty() || self.assertions.iter().any(Assertion::needs_body));
+
+        let body = match self.body_mode {
+            BodyMode::Skip if !needs_body => Ok(Vec::new()),
+            BodyMode::Stream if !needs_body => read_body(resp, false, phases).await,
+            _ => read_body(resp, true, phases).await,
+        };
+
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => return classify_error(&e),
+        };
+
+        if let Some(outcome) = failed_head {
+            return outcome;
+        }
+
+        if !needs_body {
+            return Outcome::Status(status);
+        }
+
+        let json = if !self.extract.is_empty() || self.assertions.iter().any(Assertion::needs_json) {
+            serde_json::from_slice::<serde_json::Value>(&body).ok()
+        } else {
+            None
+        };
+
+        if let Some(failed) = self.assertions.iter().find(|a| !a.check_body(&body, json.as_ref())) {
+            return Outcome::Assertion(failed.name());
+        }
+
+        if !self.extract_into(json.as_ref(), vars) {
+            return Outcome::Extract;
+        }
+
+        Outcome::Status(status)
+    }
+
+    /// Stores every extracted value in `vars`. Returns false if the body is not JSON or a path did not match.
+    fn extract_into(&self, json: Option<&serde_json::Value>, vars: &mut Variables) -> bool {
+        if self.extract.is_empty() {
+            return true;
+        }
+
+        let Some(json) = json else {
+            return false;
+        };
+
+        for (name, path) in &self.extract {
+            match path.extract(json) {
+                Some(value) => {
+                    vars.insert(name.clone(), value);
+                }
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
+
+pub struct Endpoints {
+    list: Vec<Endpoint>,
+    /// Set for weighted requests, `None` when the endpoints are flow steps run in order
+    weights: Option<WeightedIndex<u32>>,
+    feeder: Option<Feeder>,
+}
+
+impl Endpoints {
+    /// Variables for a new virtual user, or `None` once the data file ran out.
+    fn new_virtual_user(&self, vu_id: u64) -> Option<Variables> {
+        let mut vars = template::new_variables(vu_id);
+
+        match &self.feeder {
+            Some(feeder) if feeder.per_worker() && !feeder.fill(&mut vars) => None,
+            _ => Some(vars),
+        }
+    }
+
+    /// Takes the data file row for the next iteration. Returns false once the data file ran out.
+    fn feed_iteration(&self, vars: &mut Variables) -> bool {
+        match &self.feeder {
+            Some(feeder) if !feeder.per_worker() => feeder.fill(vars),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Running,
+    /// The test stopped: no new requests, in-flight ones may finish
+    Draining,
+    /// The drain timeout passed: in-flight requests are dropped
+    Cancelled,
+}
 
+/// The end of the test as seen by the request senders. Once the shutdown fires no new requests start,
+/// and requests still in flight after `--drain-timeout` are cancelled.
+/// With `--requests` the senders also stop once the budget is spent, and the test ends when the last response arrived.
+#[derive(Clone)]
+struct Shutdown {
+    tx: broadcast::Sender<u16>,
+    stage: watch::Receiver<Stage>,
+    /// Requests that may still be sent, `None` without `--requests`
+    budget: Option<Arc<AtomicU64>>,
+}
+
+impl Shutdown {
+    fn start(shutdown_tx: &broadcast::Sender<u16>, drain_timeout: Duration, requests: Option<u64>) -> Self {
+        let (stage_tx, stage) = watch::channel(Stage::Running);
+        let mut shutdown_rx = shutdown_tx.subscribe();
+
+        tokio::spawn(async move {
+            let _ = shutdown_rx.recv().await;
+            stage_tx.send_replace(Stage::Draining);
+            sleep(drain_timeout).await;
+            stage_tx.send_replace(Stage::Cancelled);
+        });
+
+        Self {
+            tx: shutdown_tx.clone(),
+            stage,
+            budget: requests.map(|requests| Arc::new(AtomicU64::new(requests))),
+        }
+    }
+
+    /// Ends the test, e.g. because the data file ran out.
+    fn trigger(&self) {
+        let _ = self.tx.send(1);
+    }
+
+    fn is_stopping(&self) -> bool {
+        *self.stage.borrow() != Stage::Running
+    }
+
+    fn budget_spent(&self) -> bool {
+        self.budget.as_ref().is_some_and(|budget| budget.load(Ordering::Relaxed) == 0)
+    }
+
+    /// Claims one request from the budget. Returns false once all of them were claimed.
+    fn take_request(&self) -> bool {
+        match &self.budget {
+            Some(budget) => budget
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| left.checked_sub(1))
+                .is_ok(),
+            None => true,
+        }
+    }
+
+    async fn stopping(&self) {
+        let _ = self.stage.clone().wait_for(|stage| *stage != Stage::Running).await;
+    }
+
+    async fn cancelled(&self) {
+        let _ = self.stage.clone().wait_for(|stage| *stage == Stage::Cancelled).await;
+    }
+}
+
+/// Resolves to `false` if the test request failed and the test never started.
 pub fn create_sender_task(
     args: TesterArgs,
     shutdown_tx: broadcast::Sender<u16>,
-    metrics_tx: mpsc::UnboundedSender<Metrics>,
+    metrics_sink: MetricsSink,
+    missed_slots: Arc<AtomicU64>,
     client: Client,
-    request: Request,
-) -> tokio::task::JoinHandle<()> {
+    endpoints: Arc<Endpoints>,
+) -> tokio::task::JoinHandle<bool> {
     let shutdown_tx = shutdown_tx.clone();
-    let metrics_tx = metrics_tx.clone();
+    let metrics_sink = metrics_sink.clone();
 
-    let request_clone = request
-        .try_clone()
-        .unwrap_or_else(|| request.try_clone().expect("Failed to clone request"));
+    let mut test_vars = template::new_variables(0);
+    if let Some(feeder) = &endpoints.feeder {
+        test_vars.extend(feeder.first_row().iter().cloned());
+    }
+    // With `--requests` the check would be one request over the budget, so it is skipped.
+    let test_request = endpoints.list[0].build(&client, &test_vars).ok().filter(|_| args.requests.is_none());
 
-    let max_tasks = args.max_tasks;
-    let spawn_rate = args.spawn_rate_per_tick;
-    let tick_interval = args.tick_interval;
+    let load_shape = LoadShape::from_args(&args);
+    let shutdown = Shutdown::start(&shutdown_tx, args.drain_timeout, args.requests);
+    let rate = args.rate;
+    let max_in_flight = args.max_in_flight.unwrap_or(args.max_tasks);
 
     tokio::spawn(async move {
-        if let Err(e) = client.execute(request_clone).await {
-            error!("Test request failed: {}", e);
-            return;
+        // A first request that depends on variables cannot be sent up front, so it is not checked.
+        if let Some(request) = test_request {
+            let bytes_sent = body_size(&request);
+            let start = Instant::now();
+
+            if let Err(e) = client.execute(request).await {
+                error!("Test request failed: {}", e);
+                // Recorded so the error report names the cause, e.g. a rejected certificate.
+                metrics_sink.recorder().record(&Metrics::new(start, start, 0, bytes_sent, None, classify_error(&e), false));
+                return false;
+            }
         }
 
-        let mut shutdown_rx = shutdown_tx.subscribe();
-        let mut interval = interval(Duration::from_millis(tick_interval));
-        let mut total_spawned = 0;
+        match rate {
+            Some(rate) => run_open_model(
+                rate,
+                max_in_flight,
+                &shutdown,
+                &metrics_sink,
+                &missed_slots,
+                &client,
+                &endpoints,
+            ).await,
+            None => run_closed_model(
+                load_shape,
+                &shutdown,
+                &metrics_sink,
+                &client,
+                &endpoints,
+            ).await,
+        }
 
-        loop {
-            tokio::select! {
-                Ok(_) = shutdown_rx.recv() => break,
+        true
+    })
+}
+
+/// Spawns long-lived workers that each send a request, wait for the response and repeat.
+/// With `--requests` or `--iterations-per-worker` it returns once no more requests are due.
+async fn run_closed_model(
+    load_shape: LoadShape,
+    shutdown: &Shutdown,
+    metrics_sink: &MetricsSink,
+    client: &Client,
+    endpoints: &Arc<Endpoints>,
+) {
+    let mut interval = interval(load_shape.tick_interval);
+    let started = Instant::now();
+    // Dropping a worker's stop sender tells that worker to exit.
+    let mut workers: Vec<oneshot::Sender<()>> = Vec::new();
+    let mut next_vu_id: u64 = 0;
 
-                _ = interval.tick() => {
-                    if total_spawned >= max_tasks {
-                        continue;
+    loop {
+        tokio::select! {
+            _ = shutdown.stopping() => break,
+
+            _ = interval.tick() => {
+                if shutdown.budget_spent() {
+                    break;
+                }
+
+                let target = load_shape.target_tasks(started.elapsed());
+
+                if target <= workers.len() {
+                    // A worker that ran all its iterations dropped its stop receiver.
+                    let running = workers.iter().filter(|worker| !worker.is_closed()).count();
+                    if load_shape.iterations_done(next_vu_id as usize, running) {
+                        break;
                     }
+                    workers.truncate(target);
+                    continue;
+                }
+
+                for _ in workers.len()..target {
+                    let (stop_tx, stop_rx) = oneshot::channel();
+                    workers.push(stop_tx);
+
+                    spawn_worker(
+                        next_vu_id,
+                        &load_shape,
+                        shutdown,
+                        metrics_sink,
+                        client,
+                        endpoints,
+                        stop_rx,
+                    );
+                    next_vu_id += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Launches requests on a fixed schedule regardless of how long responses take.
+/// Slots that come due while `max_in_flight` requests are outstanding are counted as missed.
+async fn run_open_model(
+    rate: u64,
+    max_in_flight: usize,
+    shutdown: &Shutdown,
+    metrics_sink: &MetricsSink,
+    missed_slots: &Arc<AtomicU64>,
+    client: &Client,
+    endpoints: &Arc<Endpoints>,
+) {
+    let in_flight = Arc::new(Semaphore::new(max_in_flight));
+    // Arrivals are short-lived, so they share one recorder instead of each sending a batch.
+    let recorder = Arc::new(metrics_sink.recorder());
+    let started = Instant::now();
+    let slot_offset = |slot: u64| Duration::from_nanos((slot as u128 * 1_000_000_000 / rate as u128) as u64);
+    let mut slot: u64 = 0;
+
+    loop {
+        tokio::select! {
+            _ = shutdown.stopping() => break,
+            _ = sleep_until(started + slot_offset(slot)) => {}
+        }
+
+        if shutdown.budget_spent() {
+            break;
+        }
+
+        let now = Instant::now();
+
+        while started + slot_offset(slot) <= now {
+            let intended_start = started + slot_offset(slot);
+            let vu_id = slot;
+            slot += 1;
 
-                    let remaining = max_tasks - total_spawned;
-                    let batch = remaining.min(spawn_rate);
-
-                    for _ in 0..batch {
-                        total_spawned += 1;
-
-                        let shutdown_tx = shutdown_tx.clone();
-                        let metrics_tx = metrics_tx.clone();
-                        let client = client.clone();
-                        let req = request.try_clone().unwrap();
-
-                        tokio::spawn(async move {
-                            let mut shutdown_rx = shutdown_tx.subscribe();
-
-                            loop {
-                                tokio::select! {
-                                    Ok(_) = shutdown_rx.recv() => break,
-                                    _ = async {
-                                        let start = Instant::now();
-                                        let status = match client.execute(req.try_clone().unwrap()).await {
-                                            Ok(resp) => resp.status().as_u16(),
-                                            Err(_) => 500,
-                                        };
-                                        let _ = metrics_tx.send(Metrics::new(start, status));
-                                    } => {}
-                                }
-
-                                sleep(Duration::from_millis(100)).await;
-                            }
-                        });
+            let permit = match in_flight.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    missed_slots.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            };
+
+            let shutdown = shutdown.clone();
+            let recorder = recorder.clone();
+            let client = client.clone();
+            let endpoints = endpoints.clone();
+
+            tokio::spawn(async move {
+                let _permit = permit;
+                // Every arrival is a new virtual user with its own variables.
+                let Some(mut vars) = endpoints.new_virtual_user(vu_id) else {
+                    shutdown.trigger();
+                    return;
+                };
+
+                if !run_iteration(&client, &endpoints, &mut vars, intended_start, &recorder, &shutdown).await {
+                    shutdown.trigger();
+                }
+            });
+        }
+    }
+
+    // Every arrival gave its permit back once its requests finished or were cancelled.
+    let _ = in_flight.acquire_many(u32::try_from(max_in_flight).unwrap_or(u32::MAX)).await;
+    recorder.flush().await;
+}
+
+fn spawn_worker(
+    vu_id: u64,
+    load_shape: &LoadShape,
+    shutdown: &Shutdown,
+    metrics_sink: &MetricsSink,
+    client: &Client,
+    endpoints: &Arc<Endpoints>,
+    mut stop_rx: oneshot::Receiver<()>,
+) {
+    let shutdown = shutdown.clone();
+    let recorder = metrics_sink.recorder();
+    let client = client.clone();
+    let endpoints = endpoints.clone();
+    let think_time = load_shape.think_time;
+    let iterations = load_shape.iterations;
+
+    tokio::spawn(async move {
+        // Variables live as long as the worker, so values extracted in one iteration stay usable in the next.
+        let Some(mut vars) = endpoints.new_virtual_user(vu_id) else {
+            shutdown.trigger();
+            return;
+        };
+
+        let mut completed: u64 = 0;
+
+        loop {
+            if !matches!(stop_rx.try_recv(), Err(TryRecvError::Empty)) || shutdown.is_stopping() || shutdown.budget_spent() {
+                break;
+            }
+            if iterations.is_some_and(|iterations| completed >= iterations) {
+                break;
+            }
+
+            if !run_iteration(&client, &endpoints, &mut vars, Instant::now(), &recorder, &shutdown).await {
+                shutdown.trigger();
+                break;
+            }
+            completed += 1;
+
+            if !think_time.is_zero() {
+                // A load pattern that scales down should not wait for a long pause to end.
+                tokio::select! {
+                    _ = shutdown.stopping() => break,
+                    _ = &mut stop_rx => break,
+                    _ = sleep(think_time.sample()) => {}
+                }
+            }
+        }
+
+        recorder.flush().await;
+    });
+}
+
+/// Sends one weighted request, or walks the whole flow and stops at the first failed step.
+/// A flow also stops once the test is stopping or the `--requests` budget is spent.
+/// Returns false if the data file ran out and the test should stop.
+async fn run_iteration(
+    client: &Client,
+    endpoints: &Endpoints,
+    vars: &mut Variables,
+    intended_start: Instant,
+    recorder: &MetricsRecorder,
+    shutdown: &Shutdown,
+) -> bool {
+    if !endpoints.feed_iteration(vars) {
+        return false;
+    }
+
+    if let Some(weights) = &endpoints.weights {
+        if !shutdown.take_request() {
+            return true;
+        }
+        let index = weights.sample(&mut rand::thread_rng());
+        send_request(client, endpoints, index, vars, intended_start, recorder, shutdown).await;
+        return true;
+    }
+
+    let mut intended_start = intended_start;
+
+    for index in 0..endpoints.list.len() {
+        if (index > 0 && shutdown.is_stopping()) || !shutdown.take_request() {
+            break;
+        }
+        if !send_request(client, endpoints, index, vars, intended_start, recorder, shutdown).await {
+            break;
+        }
+        // Later steps are only due once the previous one finished.
+        intended_start = Instant::now();
+    }
+
+    true
+}
+
+/// Sends the request and records its metrics. Returns whether it succeeded.
+/// A request still running when the drain timeout passes is recorded as cancelled.
+async fn send_request(
+    client: &Client,
+    endpoints: &Endpoints,
+    index: usize,
+    vars: &mut Variables,
+    intended_start: Instant,
+    recorder: &MetricsRecorder,
+    shutdown: &Shutdown,
+) -> bool {
+    let endpoint = &endpoints.list[index];
+
+    let start = Instant::now();
+    let mut response = None;
+    let mut bytes_sent = 0;
+    let outcome = match endpoint.build(client, vars) {
+        Ok(req) => {
+            bytes_sent = body_size(&req);
+            let https = req.url().scheme() == "https";
+
+            let exchange = async {
+                match connection::timed(client.execute(req)).await {
+                    (Ok(resp), times) => {
+                        let mut phases = Phases {
+                            dns: times.dns,
+                            connect: times.connect,
+                            // Without TLS this is only the moment between the TCP connect and the start of HTTP.
+                            tls: if https { times.tls } else { Duration::ZERO },
+                            ttfb: start.elapsed(),
+                            ..Phases::default()
+                        };
+                        let (status, version) = (resp.status().as_u16(), resp.version());
+                        let outcome = endpoint.evaluate(resp, vars, &mut phases).await;
+                        (outcome, Some(ResponseInfo { status, version, phases }))
                     }
+                    (Err(e), _) => (classify_error(&e), None),
                 }
+            };
+
+            tokio::select! {
+                (outcome, info) = exchange => {
+                    response = info;
+                    outcome
+                }
+                _ = shutdown.cancelled() => Outcome::Cancelled,
             }
         }
-    })
+        Err(outcome) => outcome,
+    };
+    let success = matches!(outcome, Outcome::Status(code) if endpoint.accepts_status(code));
+    recorder.record(&Metrics::new(intended_start, start, index, bytes_sent, response, outcome, success));
+
+    success
+}
+
+/// Size of the request body, from the body itself or from the Content-Length of a streamed
+/// multipart body. Bodies of unknown length count as zero.
+fn body_size(req: &Request) -> u64 {
+    match req.body().and_then(Body::as_bytes) {
+        Some(bytes) => bytes.len() as u64,
+        None => req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse().ok())
+            .unwrap_or(0),
+    }
+}
+
+/// Reads the whole body, recording its size and download time in `phases`. Returns it if `keep` is set.
+async fn read_body(mut resp: Response, keep: bool, phases: &mut Phases) -> Result<Vec<u8>, reqwest::Error> {
+    let headers_received = Instant::now();
+    let mut body = Vec::new();
+
+    let result = loop {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                phases.bytes_received += chunk.len() as u64;
+                if keep {
+                    body.extend_from_slice(&chunk);
+                }
+            }
+            Ok(None) => break Ok(body),
+            Err(e) => break Err(e),
+        }
+    };
+
+    phases.download = headers_received.elapsed();
+    result
+}
+
+fn classify_error(e: &reqwest::Error) -> Outcome {
+    if e.is_timeout() {
+        Outcome::Timeout
+    } else if let Some(outcome) = tls_error(e) {
+        outcome
+    } else if e.is_connect() {
+        Outcome::Connect
+    } else if e.is_body() || e.is_decode() {
+        Outcome::Body
+    } else if e.is_redirect() {
+        Outcome::Redirect
+    } else if e.is_request() || e.is_builder() {
+        Outcome::Request
+    } else {
+        Outcome::Other
+    }
+}
+
+/// reqwest does not flag TLS failures, so look for them in the error's source chain.
+/// Rejected certificates are told apart from other handshake failures.
+fn tls_error(e: &reqwest::Error) -> Option<Outcome> {
+    let mut source = std::error::Error::source(e);
+
+    while let Some(err) = source {
+        let message = err.to_string().to_lowercase();
+        if ["certificate", "self signed", "self-signed", "unknown ca"].iter().any(|needle| message.contains(needle)) {
+            return Some(Outcome::Certificate);
+        }
+        if ["tls", "ssl", "handshake"].iter().any(|needle| message.contains(needle)) {
+            return Some(Outcome::Tls);
+        }
+        source = err.source();
+    }
+
+    None
+}
+
+/// Applies --cacert, --cert/--key, --insecure and --tls-min-version.
+fn configure_tls(mut client_builder: ClientBuilder, args: &TesterArgs) -> Result<ClientBuilder, String> {
+    let read = |path: &str| std::fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path, e));
+
+    if let Some(path) = &args.cacert {
+        let certificates = Certificate::from_pem_bundle(&read(path)?)
+            .map_err(|e| format!("Invalid CA certificates in '{}': {}", path, e))?;
+
+        if certificates.is_empty() {
+            return Err(format!("'{}' does not contain any PEM certificates", path));
+        }
+
+        for certificate in certificates {
+            client_builder = client_builder.add_root_certificate(certificate);
+        }
+    }
+
+    if let Some(path) = &args.cert {
+        let identity = match &args.key {
+            Some(key_path) => Identity::from_pkcs8_pem(&read(path)?, &read(key_path)?),
+            None => Identity::from_pkcs12_der(&read(path)?, &args.cert_password),
+        }
+        .map_err(|e| format!("Invalid client certificate '{}': {}", path, e))?;
+
+        client_builder = client_builder.identity(identity);
+    }
+
+    if args.insecure {
+        client_builder = client_builder
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true);
+    }
+
+    if let Some(version) = args.tls_min_version {
+        client_builder = client_builder.min_tls_version(match version {
+            TlsVersion::Tls1_0 => tls::Version::TLS_1_0,
+            TlsVersion::Tls1_1 => tls::Version::TLS_1_1,
+            TlsVersion::Tls1_2 => tls::Version::TLS_1_2,
+        });
+    }
+
+    Ok(client_builder)
 }
@@ -0,0 +1,194 @@
+use std::{f64::consts::PI, time::Duration};
+
+use rand::Rng;
+
+use crate::args::{parse_duration, LoadPattern, TesterArgs};
+
+/// Pause of a closed-model task between iterations, like a user reading a page before the next click.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThinkTime {
+    Fixed(Duration),
+    /// Uniformly distributed between `min` and `max`, inclusive
+    Uniform { min: Duration, max: Duration },
+    /// Exponentially distributed with the given mean, so each task's iterations arrive as a Poisson process
+    Exponential { mean: Duration },
+}
+
+impl std::str::FromStr for ThinkTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(mean) = s.strip_prefix("exp:").or_else(|| s.strip_prefix("exponential:")) {
+            return Ok(ThinkTime::Exponential { mean: parse_duration(mean)? });
+        }
+
+        match s.split_once('-') {
+            Some((min, max)) => {
+                let (min, max) = (parse_duration(min)?, parse_duration(max)?);
+                if min > max {
+                    return Err(format!("Invalid think time '{}': the minimum is larger than the maximum", s));
+                }
+                Ok(ThinkTime::Uniform { min, max })
+            }
+            None => Ok(ThinkTime::Fixed(parse_duration(s)?)),
+        }
+    }
+}
+
+impl ThinkTime {
+    pub fn is_zero(&self) -> bool {
+        matches!(self, ThinkTime::Fixed(pause) if pause.is_zero())
+    }
+
+    /// Draws the pause before a task's next iteration.
+    pub fn sample(&self) -> Duration {
+        match *self {
+            ThinkTime::Fixed(pause) => pause,
+            ThinkTime::Uniform { min, max } => rand::thread_rng().gen_range(min..=max),
+            ThinkTime::Exponential { mean } => {
+                // Inverse transform sampling; `1 - u` is in (0, 1], so the logarithm is finite.
+                let u: f64 = rand::thread_rng().gen();
+                mean.mul_f64(-(1.0 - u).ln())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoadShape {
+    pub pattern: LoadPattern,
+    pub max_tasks: usize,
+    pub base_tasks: usize,
+    pub spawn_rate: usize,
+    pub tick_interval: Duration,
+    pub period: Duration,
+    pub step_size: usize,
+    pub spike_duration: Duration,
+    pub think_time: ThinkTime,
+    /// Iterations after which a task exits, `None` to keep going until the test ends
+    pub iterations: Option<u64>,
+}
+
+impl LoadShape {
+    pub fn from_args(args: &TesterArgs) -> Self {
+        Self {
+            pattern: args.load_pattern,
+            max_tasks: args.max_tasks,
+            base_tasks: args.base_tasks.min(args.max_tasks),
+            spawn_rate: args.spawn_rate_per_tick,
+            tick_interval: Duration::from_millis(args.tick_interval.max(1)),
+            period: Duration::from_secs(args.pattern_period.max(1)),
+            step_size: args.step_size,
+            spike_duration: Duration::from_secs(args.spike_duration),
+            think_time: args.think_time,
+            iterations: args.iterations_per_worker,
+        }
+    }
+
+    /// Number of tasks that should be active `elapsed` after the test started.
+    pub fn target_tasks(&self, elapsed: Duration) -> usize {
+        let period = self.period.as_secs_f64();
+        let elapsed_secs = elapsed.as_secs_f64();
+
+        let target = match self.pattern {
+            LoadPattern::RampUp => {
+                let ticks = (elapsed.as_millis() / self.tick_interval.as_millis()) as usize + 1;
+                self.spawn_rate.saturating_mul(ticks)
+            }
+            LoadPattern::Constant => self.max_tasks,
+            LoadPattern::Spike => {
+                // Baseline for most of each period, peak for its last `spike_duration` seconds.
+                let position = elapsed_secs % period;
+                let spike_start = (period - self.spike_duration.as_secs_f64()).max(0.0);
+                if position >= spike_start {
+                    self.max_tasks
+                } else {
+                    self.base_tasks
+                }
+            }
+            LoadPattern::Wave => {
+                // Starts at baseline, peaks half way through each period.
+                let amplitude = (self.max_tasks - self.base_tasks) as f64;
+                let phase = (1.0 - (2.0 * PI * elapsed_secs / period).cos()) / 2.0;
+                self.base_tasks + (amplitude * phase).round() as usize
+            }
+            LoadPattern::Step => {
+                let steps = (elapsed_secs / period).floor() as usize + 1;
+                self.base_tasks.saturating_add(self.step_size.saturating_mul(steps))
+            }
+        };
+
+        target.min(self.max_tasks)
+    }
+
+    /// Most tasks the pattern ever asks for.
+    fn peak_tasks(&self) -> usize {
+        match self.pattern {
+            LoadPattern::RampUp if self.spawn_rate == 0 => 0,
+            LoadPattern::Step if self.step_size == 0 => self.base_tasks,
+            _ => self.max_tasks,
+        }
+    }
+
+    /// Whether a test with `--iterations-per-worker` is over: the pattern started every task it ever will,
+    /// and all of them ran their iterations.
+    pub fn iterations_done(&self, started: usize, running: usize) -> bool {
+        self.iterations.is_some() && started > 0 && started >= self.peak_tasks() && running == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(pattern: LoadPattern) -> LoadShape {
+        LoadShape {
+            pattern,
+            max_tasks: 5,
+            base_tasks: 0,
+            spawn_rate: 1,
+            tick_interval: Duration::from_millis(100),
+            period: Duration::from_secs(10),
+            step_size: 1,
+            spike_duration: Duration::from_secs(2),
+            think_time: ThinkTime::Fixed(Duration::ZERO),
+            iterations: Some(3),
+        }
+    }
+
+    #[test]
+    fn periodic_patterns_start_without_tasks() {
+        assert_eq!(shape(LoadPattern::Spike).target_tasks(Duration::ZERO), 0);
+        assert_eq!(shape(LoadPattern::Wave).target_tasks(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn iterations_are_not_done_before_a_task_started() {
+        for pattern in [LoadPattern::RampUp, LoadPattern::Constant, LoadPattern::Spike, LoadPattern::Wave, LoadPattern::Step] {
+            assert!(!shape(pattern).iterations_done(0, 0));
+        }
+    }
+
+    #[test]
+    fn iterations_are_not_done_while_the_pattern_still_starts_tasks() {
+        let ramp_up = shape(LoadPattern::RampUp);
+        assert!(!ramp_up.iterations_done(2, 0));
+        assert!(!ramp_up.iterations_done(5, 1));
+        assert!(ramp_up.iterations_done(5, 0));
+    }
+
+    #[test]
+    fn iterations_are_done_once_a_flat_step_pattern_started_its_base_tasks() {
+        let step = LoadShape { base_tasks: 2, step_size: 0, ..shape(LoadPattern::Step) };
+        assert!(!step.iterations_done(1, 0));
+        assert!(step.iterations_done(2, 0));
+    }
+
+    #[test]
+    fn tests_without_iterations_are_never_done() {
+        let constant = LoadShape { iterations: None, ..shape(LoadPattern::Constant) };
+        assert!(!constant.iterations_done(5, 0));
+    }
+}
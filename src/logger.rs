@@ -1,9 +1,16 @@
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::{filter::LevelFilter, fmt, layer::SubscriberExt, Layer, Registry};
+
+use crate::connection::ConnectionEvents;
 
 pub fn init_logging() {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(tracing::Level::INFO)
-        .finish();
+    let subscriber = Registry::default()
+        .with(
+            fmt::layer()
+                // Keeps stdout for the summary and threshold table, e.g. `--summary markdown > summary.md`.
+                .with_writer(std::io::stderr)
+                .with_filter(LevelFilter::INFO),
+        )
+        .with(ConnectionEvents.with_filter(ConnectionEvents::filter()));
 
     tracing::subscriber::set_global_default(subscriber)
         .expect("Failed to set global default subscriber");
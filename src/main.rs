@@ -9,13 +9,22 @@ mod metrics;
 mod shutdown;
 mod charts;
 mod logger;
+mod load;
+mod report;
+mod scenario;
+mod thresholds;
+mod assertions;
+mod template;
+mod extract;
+mod feeder;
+mod connection;
 
-use args::TesterArgs;
-use tracing::info;
-use std::error::Error;
-use clap::Parser;
+use args::{LoadPattern, TesterArgs};
+use tracing::{error, info, warn};
+use std::{error::Error, io::IsTerminal, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use tokio::sync::{broadcast, mpsc, watch};
-use crate::{charts::plot_metrics, metrics::Metrics, ui::{setup_render_ui, UiData}};
+use crate::{charts::plot_metrics, metrics::{MetricsBatch, MetricsSink, METRICS_CHANNEL_CAPACITY}, report::Summary, scenario::Scenario, ui::{setup_progress_output, setup_render_ui, UiData}};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -23,43 +32,108 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let args = TesterArgs::parse();
 
+    // Spike and wave stop tasks when they scale down, before those ran all their iterations.
+    if args.iterations_per_worker.is_some() && matches!(args.load_pattern, LoadPattern::Spike | LoadPattern::Wave) {
+        TesterArgs::command()
+            .error(ErrorKind::ArgumentConflict, "--iterations-per-worker cannot be used with the spike and wave load patterns")
+            .exit();
+    }
+
+    let scenario = match Scenario::from_args(&args) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let (shutdown_tx, _) = broadcast::channel::<u16>(1);
     let (ui_tx, _) = watch::channel(UiData::default());
-    let (metrics_tx, metrics_rx) = mpsc::unbounded_channel::<Metrics>();
+    let (metrics_tx, metrics_rx) = mpsc::channel::<MetricsBatch>(METRICS_CHANNEL_CAPACITY);
+    let missed_slots = Arc::new(AtomicU64::new(0));
+    let metrics_sink = MetricsSink::new(metrics_tx);
 
-    let shutdown_handle = shutdown::setup_shutdown_handler(&shutdown_tx);
-    let render_ui_handle = setup_render_ui(
-        &args,
-        &shutdown_tx,
-        &ui_tx
-    );
-    let (metrics_aggregator_handle, metrics_handle) = metrics::setup_metrics_collector(
+    // The dashboard needs a terminal; in CI, cron or under nohup progress goes to stderr instead.
+    let headless = args.no_tui || !std::io::stdout().is_terminal();
+
+    shutdown::setup_signal_handler(&shutdown_tx, headless);
+    let shutdown_handle = shutdown::setup_shutdown_handler(&shutdown_tx, headless);
+    let render_ui_handle = if headless {
+        setup_progress_output(&args, &shutdown_tx, &ui_tx)
+    } else {
+        setup_render_ui(
+            &args,
+            &shutdown_tx,
+            &ui_tx
+        )
+    };
+    let metrics_handle = metrics::setup_metrics_collector(
         &args,
         &shutdown_tx,
         metrics_rx,
+        &metrics_sink,
+        &missed_slots,
+        scenario.names(),
         &ui_tx
     );
     let request_sender_handle = http::setup_request_sender(
         &args,
+        &scenario,
         &shutdown_tx,
-        &metrics_tx
+        &metrics_sink,
+        &missed_slots
     );
 
-    if request_sender_handle.is_none() {
-        return Ok(());
-    }
+    // The sender logged why it could not start and sent the shutdown; let the dashboard restore the terminal first.
+    let Some(request_sender_handle) = request_sender_handle else {
+        let _ = tokio::join!(shutdown_handle, render_ui_handle);
+        std::process::exit(1);
+    };
 
-    let (_, _, _, metrics_result, _) = tokio::join!(
+    // The collector finishes once every request sender dropped its copy.
+    drop(metrics_sink);
+
+    let (_, _, metrics_result, sender_result) = tokio::join!(
         shutdown_handle,
         render_ui_handle,
-        metrics_aggregator_handle,
         metrics_handle,
-        request_sender_handle.unwrap()
+        request_sender_handle
     );
 
     let metrics = metrics_result.expect("Metrics collector failed");
+    let test_request_failed = matches!(sender_result, Ok(false));
+
+    let errors = metrics.error_breakdown();
+    if !errors.is_empty() {
+        info!("❌ Errors by category:");
+        for (outcome, count) in errors {
+            info!("   {}: {}", outcome, count);
+        }
+    }
+
+    if metrics.lost_samples > 0 {
+        warn!("⚠️ {} results never reached the collector, the summary and charts are incomplete", metrics.lost_samples);
+    }
 
-    if !metrics.is_empty() {
+    let mut summary = Summary::new(&metrics, &args, missed_slots.load(Ordering::Relaxed));
+
+    summary.thresholds = args.thresholds
+        .iter()
+        .map(|threshold| threshold.evaluate(&summary))
+        .collect();
+
+    if let Some(ref path) = args.output_json {
+        match summary.write_json(path).await {
+            Ok(()) => info!("📝 Summary saved to {}", path),
+            Err(e) => error!("Failed to write summary to '{}': {}", path, e),
+        }
+    }
+
+    if let Some(format) = args.summary {
+        println!("{}", summary.render(format));
+    }
+
+    if !args.no_charts && !metrics.is_empty() {
         info!("📈 Plotting charts...");
 
         plot_metrics(&metrics, &args).await.expect("Failed to plot charts");
@@ -67,5 +141,23 @@ async fn main() -> Result<(), Box<dyn Error>> {
         info!("📈 Charts saved in {}", args.charts_path);
     }
 
+    if !summary.thresholds.is_empty() {
+        println!("{}", thresholds::render_results(&summary.thresholds));
+
+        if summary.requests.total == 0 {
+            error!("❌ No requests completed, so no threshold can pass");
+        }
+    }
+
+    if test_request_failed {
+        error!("❌ The test request failed, so the test was not run");
+        std::process::exit(1);
+    }
+
+    if summary.thresholds.iter().any(|result| !result.passed) {
+        error!("❌ One or more thresholds were breached");
+        std::process::exit(thresholds::THRESHOLDS_FAILED_EXIT_CODE);
+    }
+
     std::process::exit(0);
 }
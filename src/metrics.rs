@@ -1,22 +1,129 @@
-use std::{collections::VecDeque, ops::RangeInclusive, time::Duration};
+use std::{collections::{BTreeMap, VecDeque}, ops::RangeInclusive, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, MutexGuard, PoisonError, Weak}, time::Duration};
 
-use tokio::{sync::{broadcast, mpsc, watch}, task::JoinHandle, time::Instant};
+use hdrhistogram::Histogram;
+use reqwest::Version;
+use serde::Serialize;
+use tokio::{sync::{broadcast, mpsc, watch}, task::JoinHandle, time::{interval, sleep_until, Instant}};
 
 use crate::{args::TesterArgs, ui::UiData};
 
+/// Highest latency the histograms can hold (microseconds); larger values are clamped.
+const MAX_TRACKED_LATENCY_US: u64 = 3_600_000_000;
+const HISTOGRAM_SIGNIFICANT_FIGURES: u8 = 3;
+/// Number of finished seconds shown in the live latency chart.
+const UI_HISTORY_SECONDS: usize = 60;
+/// How long after the drain timeout the collector still waits for the last metrics.
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+/// How often the collector takes the results every request sender gathered in the meantime.
+const COLLECT_INTERVAL: Duration = Duration::from_millis(100);
+/// Final batches of request senders that finished, waiting for the collector.
+pub const METRICS_CHANNEL_CAPACITY: usize = 1_024;
+
+/// How a request ended: an HTTP status from the server or the kind of transport failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    Status(u16),
+    Timeout,
+    Connect,
+    Tls,
+    /// The server's certificate was rejected, or ours was rejected by the server
+    Certificate,
+    Body,
+    Redirect,
+    Request,
+    /// A template referenced a variable that was not set, so nothing was sent
+    Template,
+    /// An extraction rule did not match the response body
+    Extract,
+    /// The named `--assert` check failed
+    Assertion(&'static str),
+    /// Still in flight when the drain timeout after the end of the test passed
+    Cancelled,
+    Other,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Status(code) => write!(f, "HTTP {}", code),
+            Outcome::Timeout => write!(f, "timeout"),
+            Outcome::Connect => write!(f, "connect error"),
+            Outcome::Tls => write!(f, "TLS error"),
+            Outcome::Certificate => write!(f, "TLS certificate error"),
+            Outcome::Body => write!(f, "body error"),
+            Outcome::Redirect => write!(f, "redirect error"),
+            Outcome::Request => write!(f, "request error"),
+            Outcome::Template => write!(f, "template error"),
+            Outcome::Extract => write!(f, "extraction failed"),
+            Outcome::Assertion(name) => write!(f, "assertion {}", name),
+            Outcome::Cancelled => write!(f, "cancelled"),
+            Outcome::Other => write!(f, "other error"),
+        }
+    }
+}
+
+/// Where the time of a request with a response went.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Phases {
+    /// DNS lookup, zero when a pooled connection was reused or the host is an IP address
+    pub dns: Duration,
+    /// TCP connect, zero when a pooled connection was reused
+    pub connect: Duration,
+    /// TLS handshake, zero when a pooled connection was reused or for plain HTTP
+    pub tls: Duration,
+    /// Time to first byte: actual send until the response headers arrived, including `dns`, `connect` and `tls`
+    pub ttfb: Duration,
+    /// Response headers until the body was read completely
+    pub download: Duration,
+    /// Size of the response body
+    pub bytes_received: u64,
+}
+
+/// What is known about a response that was received.
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseInfo {
+    pub status: u16,
+    /// Negotiated protocol
+    pub version: Version,
+    pub phases: Phases,
+}
+
 #[derive(Clone, Debug)]
 pub struct Metrics {
-    pub start: Instant,
+    /// Index of the scenario request that was sent
+    pub endpoint: usize,
+    /// Service time: actual send until completion
     pub response_time: Duration,
-    pub status_code: u16
+    /// Response time corrected for coordinated omission: intended send until completion
+    pub corrected_response_time: Duration,
+    /// Size of the request body
+    pub bytes_sent: u64,
+    /// Set if a response was received
+    pub response: Option<ResponseInfo>,
+    pub outcome: Outcome,
+    pub success: bool
 }
 
 impl Metrics {
-    pub fn new(start: Instant, status_code: u16) -> Self {
+    pub fn new(
+        intended_start: Instant,
+        start: Instant,
+        endpoint: usize,
+        bytes_sent: u64,
+        response: Option<ResponseInfo>,
+        outcome: Outcome,
+        success: bool,
+    ) -> Self {
+        let now = Instant::now();
+
         Self {
-            start,
-            response_time: Instant::now() - start,
-            status_code
+            endpoint,
+            response_time: now - start,
+            corrected_response_time: now - intended_start,
+            bytes_sent,
+            response,
+            outcome,
+            success
         }
     }
 }
@@ -41,139 +148,640 @@ impl std::str::FromStr for MetricsRange {
     }
 }
 
+fn new_histogram() -> Histogram<u64> {
+    Histogram::new_with_max(MAX_TRACKED_LATENCY_US, HISTOGRAM_SIGNIFICANT_FIGURES)
+        .expect("Invalid histogram bounds")
+}
+
+/// Latency distribution in milliseconds, read from a histogram.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LatencyPercentiles {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub p99_9: f64,
+    pub p99_99: f64,
+    pub max: f64,
+}
+
+impl LatencyPercentiles {
+    pub fn from_histogram(histogram: &Histogram<u64>) -> Self {
+        if histogram.is_empty() {
+            return Self::default();
+        }
+
+        let ms = |us: u64| us as f64 / 1000.0;
+
+        Self {
+            mean: histogram.mean() / 1000.0,
+            p50: ms(histogram.value_at_quantile(0.50)),
+            p90: ms(histogram.value_at_quantile(0.90)),
+            p99: ms(histogram.value_at_quantile(0.99)),
+            p99_9: ms(histogram.value_at_quantile(0.999)),
+            p99_99: ms(histogram.value_at_quantile(0.9999)),
+            max: ms(histogram.max()),
+        }
+    }
+}
+
+/// Whole-run histograms of the phases of every request with a response.
+pub struct PhaseHistograms {
+    pub dns: Histogram<u64>,
+    pub connect: Histogram<u64>,
+    pub tls: Histogram<u64>,
+    pub ttfb: Histogram<u64>,
+    pub download: Histogram<u64>,
+}
+
+impl PhaseHistograms {
+    fn new() -> Self {
+        Self {
+            dns: new_histogram(),
+            connect: new_histogram(),
+            tls: new_histogram(),
+            ttfb: new_histogram(),
+            download: new_histogram(),
+        }
+    }
+
+    fn record(&mut self, phases: &Phases) {
+        self.dns.saturating_record(phases.dns.as_micros() as u64);
+        self.connect.saturating_record(phases.connect.as_micros() as u64);
+        self.tls.saturating_record(phases.tls.as_micros() as u64);
+        self.ttfb.saturating_record(phases.ttfb.as_micros() as u64);
+        self.download.saturating_record(phases.download.as_micros() as u64);
+    }
+}
+
+/// Mean duration of each phase in milliseconds, over the responses of one second.
+/// The phases add up to the mean service time of those responses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseMeans {
+    pub dns: f64,
+    pub connect: f64,
+    pub tls: f64,
+    /// Waiting for the server, i.e. TTFB without DNS, TCP connect and TLS handshake
+    pub wait: f64,
+    pub download: f64,
+}
+
+fn add_counts<K: Ord>(into: &mut BTreeMap<K, u64>, from: BTreeMap<K, u64>) {
+    for (key, count) in from {
+        *into.entry(key).or_insert(0) += count;
+    }
+}
+
+/// Counters of a batch, or of one second of the test once batches were merged.
+#[derive(Default)]
+struct Counts {
+    requests: u64,
+    successful_requests: u64,
+    assertion_failures: BTreeMap<&'static str, u64>,
+    bytes_sent: u64,
+    responses: u64,
+    /// Sum of the phases of every response
+    phases: Phases,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.requests += other.requests;
+        self.successful_requests += other.successful_requests;
+        add_counts(&mut self.assertion_failures, other.assertion_failures);
+        self.bytes_sent += other.bytes_sent;
+        self.responses += other.responses;
+        self.phases.dns += other.phases.dns;
+        self.phases.connect += other.phases.connect;
+        self.phases.tls += other.phases.tls;
+        self.phases.ttfb += other.phases.ttfb;
+        self.phases.download += other.phases.download;
+        self.phases.bytes_received += other.phases.bytes_received;
+    }
+
+    fn phase_means(&self) -> PhaseMeans {
+        if self.responses == 0 {
+            return PhaseMeans::default();
+        }
+
+        let mean_ms = |total: Duration| total.as_secs_f64() * 1000.0 / self.responses as f64;
+
+        let setup = self.phases.dns + self.phases.connect + self.phases.tls;
+
+        PhaseMeans {
+            dns: mean_ms(self.phases.dns),
+            connect: mean_ms(self.phases.connect),
+            tls: mean_ms(self.phases.tls),
+            wait: mean_ms(self.phases.ttfb.saturating_sub(setup)),
+            download: mean_ms(self.phases.download),
+        }
+    }
+}
+
+/// Live counters and histograms for one second of the test.
+struct SecondStats {
+    counts: Counts,
+    service: Histogram<u64>,
+    corrected: Histogram<u64>,
+}
+
+impl SecondStats {
+    fn new() -> Self {
+        Self {
+            counts: Counts::default(),
+            service: new_histogram(),
+            corrected: new_histogram(),
+        }
+    }
+}
+
+/// Latencies of one request, kept until the collector records them in its histograms.
+struct Sample {
+    /// Second of the test the request completed in
+    second: u64,
+    endpoint: usize,
+    success: bool,
+    response_time: Duration,
+    corrected_response_time: Duration,
+    /// Set if a response was received
+    phases: Option<Phases>,
+}
+
+/// Results a request sender gathered since the collector last took them. Counters are summed up per second
+/// by the sender, latencies stay samples because a set of histograms is too large to hand over several times a second.
+#[derive(Default)]
+pub struct MetricsBatch {
+    seconds: BTreeMap<u64, Counts>,
+    status_codes: BTreeMap<u16, u64>,
+    versions: BTreeMap<Version, u64>,
+    errors: BTreeMap<Outcome, u64>,
+    samples: Vec<Sample>,
+}
+
+impl MetricsBatch {
+    fn record(&mut self, second: u64, metrics: &Metrics) {
+        let counts = self.seconds.entry(second).or_default();
+        counts.requests += 1;
+        counts.bytes_sent += metrics.bytes_sent;
+
+        if let Some(response) = &metrics.response {
+            *self.status_codes.entry(response.status).or_insert(0) += 1;
+            *self.versions.entry(response.version).or_insert(0) += 1;
+
+            let phases = &response.phases;
+            counts.responses += 1;
+            counts.phases.dns += phases.dns;
+            counts.phases.connect += phases.connect;
+            counts.phases.tls += phases.tls;
+            counts.phases.ttfb += phases.ttfb;
+            counts.phases.download += phases.download;
+            counts.phases.bytes_received += phases.bytes_received;
+        }
+
+        if metrics.success {
+            counts.successful_requests += 1;
+        } else {
+            *self.errors.entry(metrics.outcome).or_insert(0) += 1;
+
+            if let Outcome::Assertion(name) = metrics.outcome {
+                *counts.assertion_failures.entry(name).or_insert(0) += 1;
+            }
+        }
+
+        self.samples.push(Sample {
+            second,
+            endpoint: metrics.endpoint,
+            success: metrics.success,
+            response_time: metrics.response_time,
+            corrected_response_time: metrics.corrected_response_time,
+            phases: metrics.response.map(|response| response.phases),
+        });
+    }
+
+    fn requests(&self) -> u64 {
+        self.samples.len() as u64
+    }
+
+    fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// What the collector shares with every request sender. It does not hold the channel, so the channel closes
+/// once the last sender is gone.
+struct SinkState {
+    /// Seconds of the test are counted from here
+    started: Instant,
+    /// Batch of every recorder, taken by the collector every `COLLECT_INTERVAL`
+    batches: Mutex<Vec<Weak<Mutex<MetricsBatch>>>>,
+    lost_samples: AtomicU64,
+}
+
+impl SinkState {
+    /// Takes the results gathered by every recorder that is still alive.
+    fn take_batches(&self) -> Vec<MetricsBatch> {
+        let mut batches = lock(&self.batches);
+        batches.retain(|batch| batch.strong_count() > 0);
+
+        batches
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|batch| std::mem::take(&mut *lock(&batch)))
+            .filter(|batch| !batch.is_empty())
+            .collect()
+    }
+}
+
+/// Where request senders leave their results. The collector takes them every `COLLECT_INTERVAL` instead of
+/// receiving a message per request; the channel only carries what a sender gathered since then when it finishes.
+#[derive(Clone)]
+pub struct MetricsSink {
+    tx: mpsc::Sender<MetricsBatch>,
+    state: Arc<SinkState>,
+}
+
+impl MetricsSink {
+    pub fn new(tx: mpsc::Sender<MetricsBatch>) -> Self {
+        Self {
+            tx,
+            state: Arc::new(SinkState {
+                started: Instant::now(),
+                batches: Mutex::new(Vec::new()),
+                lost_samples: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn recorder(&self) -> MetricsRecorder {
+        let batch = Arc::new(Mutex::new(MetricsBatch::default()));
+        lock(&self.state.batches).push(Arc::downgrade(&batch));
+
+        MetricsRecorder { sink: self.clone(), batch }
+    }
+
+    fn lose(&self, batch: &MetricsBatch) {
+        self.state.lost_samples.fetch_add(batch.requests(), Ordering::Relaxed);
+    }
+}
+
+/// Collects the results of one request sender. Results that reach the channel after the collector stopped are lost.
+pub struct MetricsRecorder {
+    sink: MetricsSink,
+    batch: Arc<Mutex<MetricsBatch>>,
+}
+
+impl MetricsRecorder {
+    pub fn record(&self, metrics: &Metrics) {
+        let second = self.sink.state.started.elapsed().as_secs();
+        lock(&self.batch).record(second, metrics);
+    }
+
+    /// Hands over the results the collector did not take yet, waiting for room in the channel.
+    pub async fn flush(&self) {
+        let batch = std::mem::take(&mut *lock(&self.batch));
+
+        if !batch.is_empty() {
+            if let Err(e) = self.sink.tx.send(batch).await {
+                self.sink.lose(&e.0);
+            }
+        }
+    }
+}
+
+impl Drop for MetricsRecorder {
+    fn drop(&mut self) {
+        let batch = std::mem::take(&mut *lock(&self.batch));
+
+        if !batch.is_empty() {
+            if let Err(e) = self.sink.tx.try_send(batch) {
+                self.sink.lose(&e.into_inner());
+            }
+        }
+    }
+}
+
+/// What is kept of a second once it is over, so memory stays bounded on long runs.
+#[derive(Debug, Clone)]
+pub struct SecondSummary {
+    pub second: u64,
+    pub requests: u64,
+    pub successful_requests: u64,
+    /// Failures per `--assert` expression, included in `errors()`
+    pub assertion_failures: BTreeMap<&'static str, u64>,
+    pub service: LatencyPercentiles,
+    pub corrected: LatencyPercentiles,
+    pub phases: PhaseMeans,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl SecondSummary {
+    pub fn errors(&self) -> u64 {
+        self.requests - self.successful_requests
+    }
+}
+
+/// Counters and histograms for one named scenario request.
+pub struct EndpointStats {
+    pub name: String,
+    pub requests: u64,
+    pub successful_requests: u64,
+    pub service: Histogram<u64>,
+    pub corrected: Histogram<u64>,
+}
+
+impl EndpointStats {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            requests: 0,
+            successful_requests: 0,
+            service: new_histogram(),
+            corrected: new_histogram(),
+        }
+    }
+}
+
+/// Whole-run histograms plus per-second summaries of every response seen by the collector.
+pub struct MetricsAggregate {
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    /// Responses per HTTP status code, including ones that failed an assertion
+    pub status_codes: BTreeMap<u16, u64>,
+    /// Responses per negotiated HTTP version
+    pub versions: BTreeMap<Version, u64>,
+    /// Outcomes of failed requests only
+    pub errors: BTreeMap<Outcome, u64>,
+    pub endpoints: Vec<EndpointStats>,
+    pub service: Histogram<u64>,
+    pub corrected: Histogram<u64>,
+    pub phases: PhaseHistograms,
+    /// Request body bytes of the whole run
+    pub bytes_sent: u64,
+    /// Response body bytes of the whole run
+    pub bytes_received: u64,
+    /// Results that never reached the collector and are missing from everything above
+    pub lost_samples: u64,
+    pub seconds: Vec<SecondSummary>,
+    /// How long the collector ran, set by `finish`
+    pub elapsed: Duration,
+    live_seconds: BTreeMap<u64, SecondStats>,
+    /// First second that was not summarized yet
+    open_from: u64,
+}
+
+impl MetricsAggregate {
+    pub fn new(endpoint_names: Vec<String>) -> Self {
+        Self {
+            total_requests: 0,
+            successful_requests: 0,
+            status_codes: BTreeMap::new(),
+            versions: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            endpoints: endpoint_names.into_iter().map(EndpointStats::new).collect(),
+            service: new_histogram(),
+            corrected: new_histogram(),
+            phases: PhaseHistograms::new(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            lost_samples: 0,
+            seconds: Vec::new(),
+            elapsed: Duration::ZERO,
+            live_seconds: BTreeMap::new(),
+            open_from: 0,
+        }
+    }
+
+    /// Adds a batch of results, each under the second it completed in. Results of a second that was
+    /// already summarized count towards the oldest second still open.
+    pub fn merge(&mut self, batch: MetricsBatch) {
+        let open_from = self.open_from;
+
+        for sample in &batch.samples {
+            let service_us = sample.response_time.as_micros() as u64;
+            let corrected_us = sample.corrected_response_time.as_micros() as u64;
+
+            self.service.saturating_record(service_us);
+            self.corrected.saturating_record(corrected_us);
+
+            let stats = self.live_seconds.entry(sample.second.max(open_from)).or_insert_with(SecondStats::new);
+            stats.service.saturating_record(service_us);
+            stats.corrected.saturating_record(corrected_us);
+
+            if let Some(phases) = &sample.phases {
+                self.phases.record(phases);
+            }
+
+            if let Some(endpoint) = self.endpoints.get_mut(sample.endpoint) {
+                endpoint.requests += 1;
+                endpoint.service.saturating_record(service_us);
+                endpoint.corrected.saturating_record(corrected_us);
+                if sample.success {
+                    endpoint.successful_requests += 1;
+                }
+            }
+        }
+
+        for (second, counts) in batch.seconds {
+            self.total_requests += counts.requests;
+            self.successful_requests += counts.successful_requests;
+            self.bytes_sent += counts.bytes_sent;
+            self.bytes_received += counts.phases.bytes_received;
+
+            let stats = self.live_seconds.entry(second.max(open_from)).or_insert_with(SecondStats::new);
+            stats.counts.add(counts);
+        }
+
+        add_counts(&mut self.status_codes, batch.status_codes);
+        add_counts(&mut self.versions, batch.versions);
+        add_counts(&mut self.errors, batch.errors);
+    }
+
+    /// Collapses every live second before `second` into a summary.
+    pub fn close_seconds_before(&mut self, second: u64) {
+        let still_live = self.live_seconds.split_off(&second);
+        let closed = std::mem::replace(&mut self.live_seconds, still_live);
+        self.open_from = self.open_from.max(second);
+
+        self.seconds.extend(closed.into_iter().map(|(second, stats)| SecondSummary {
+            second,
+            phases: stats.counts.phase_means(),
+            bytes_sent: stats.counts.bytes_sent,
+            bytes_received: stats.counts.phases.bytes_received,
+            requests: stats.counts.requests,
+            successful_requests: stats.counts.successful_requests,
+            assertion_failures: stats.counts.assertion_failures,
+            service: LatencyPercentiles::from_histogram(&stats.service),
+            corrected: LatencyPercentiles::from_histogram(&stats.corrected),
+        }));
+    }
+
+    pub fn finish(&mut self, elapsed: Duration) {
+        self.close_seconds_before(u64::MAX);
+        self.elapsed = elapsed;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_requests == 0
+    }
+
+    /// Counts of every outcome of failed requests, most frequent first.
+    pub fn error_breakdown(&self) -> Vec<(Outcome, u64)> {
+        let mut errors: Vec<(Outcome, u64)> = self.errors
+            .iter()
+            .map(|(outcome, count)| (*outcome, *count))
+            .collect();
+
+        errors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        errors
+    }
+}
+
+/// Responses that reached the collector within one slice of the live rate window.
+struct RateBucket {
+    start: Instant,
+    requests: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// Merges batches into the aggregate and the rate window, then closes the seconds that are over.
+fn ingest(aggregate: &mut MetricsAggregate, rate_window: &mut VecDeque<RateBucket>, start_time: Instant, batches: Vec<MetricsBatch>) {
+    let now = Instant::now();
+
+    if rate_window.back().is_none_or(|bucket| now.duration_since(bucket.start) >= Duration::from_millis(100)) {
+        rate_window.push_back(RateBucket { start: now, requests: 0, bytes_sent: 0, bytes_received: 0 });
+    }
+
+    for batch in batches {
+        if let Some(bucket) = rate_window.back_mut() {
+            for counts in batch.seconds.values() {
+                bucket.requests += counts.requests;
+                bucket.bytes_sent += counts.bytes_sent;
+                bucket.bytes_received += counts.phases.bytes_received;
+            }
+        }
+
+        aggregate.merge(batch);
+    }
+
+    while rate_window.front().is_some_and(|bucket| now.duration_since(bucket.start) > Duration::from_secs(60)) {
+        rate_window.pop_front();
+    }
+
+    // A second is closed once the next one is over too, which leaves room for results taken late.
+    aggregate.close_seconds_before(now.duration_since(start_time).as_secs().saturating_sub(1));
+}
+
+/// Merges the results of every request sender into per-second summaries keyed by when the requests completed.
 pub fn setup_metrics_collector(
     args: &TesterArgs,
     shutdown_tx: &broadcast::Sender<u16>,
-    mut metrics_collector_rx: mpsc::UnboundedReceiver<Metrics>,
+    mut metrics_rx: mpsc::Receiver<MetricsBatch>,
+    metrics_sink: &MetricsSink,
+    missed_slots: &Arc<AtomicU64>,
+    endpoint_names: Vec<String>,
     ui_tx: &watch::Sender<UiData>
-) -> (JoinHandle<()>, JoinHandle<Vec<Metrics>>) {
+) -> JoinHandle<MetricsAggregate> {
     let shutdown_tx_main = shutdown_tx.clone();
-    let mut shutdown_rx = shutdown_tx_main.subscribe();
     let ui_tx = ui_tx.clone();
 
     let target_duration = Duration::from_secs(args.target_duration);
-    let expected_status_code = args.expected_status_code;
-    let no_charts = args.no_charts;
+    // Requests may finish up to the drain timeout after the shutdown. The collector stops earlier once every
+    // request sender is gone, and this limit only guards against one that never finishes.
+    let drain_limit = args.drain_timeout + DRAIN_GRACE;
 
-    let (metrics_tx, mut metrics_rx) = mpsc::channel::<Metrics>(10_000);
+    let missed_slots = missed_slots.clone();
+    let sink = metrics_sink.state.clone();
 
-    let forwarder_handle = tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                Some(msg) = metrics_collector_rx.recv() => {
-                    let _ = metrics_tx.try_send(msg);
-                },
-                _ = shutdown_rx.recv() => break,
-            }
-        }
-    });
-
-    let metrics_range = args.metrics_range.clone();
-
-    let metrics_aggregator_handle = tokio::spawn(async move {
-        let mut latency_window: VecDeque<(Instant, f64)> = VecDeque::new();
-        let mut rps_window: VecDeque<(Instant, usize)> = VecDeque::new();
-        let mut current_requests = 0;
-        let mut successful_requests = 0;
-        let mut collected_metrics = Vec::new();
-        let start_time = Instant::now();
-        let mut last_ui_update = Instant::now();
+    tokio::spawn(async move {
+        let mut aggregate = MetricsAggregate::new(endpoint_names);
+        let mut rate_window: VecDeque<RateBucket> = VecDeque::new();
+        let start_time = sink.started;
+        let mut collect_interval = interval(COLLECT_INTERVAL);
+        let mut ui_interval = interval(Duration::from_millis(100));
         let mut shutdown_rx = shutdown_tx_main.subscribe();
-        let ui_tx_clone = ui_tx.clone();
+        let mut deadline: Option<Instant> = None;
 
-        let _ = ui_tx.send(UiData::new(
-            Duration::ZERO,
-            0,
-            0,
-            vec![],
-            0.0,
-            0.0,
-        ));
+        let _ = ui_tx.send(UiData::default());
 
         loop {
             tokio::select! {
-                Some(msg) = metrics_rx.recv() => {
-                    let now = Instant::now();
-                    let latency_ms = msg.response_time.as_secs_f64() * 1000.0;
-
-                    if !no_charts {
-                        let seconds_elapsed = now.duration_since(start_time).as_secs();
-                        let in_range = match &metrics_range {
-                            Some(MetricsRange(range)) => range.contains(&seconds_elapsed),
-                            None => true,
-                        };
-
-                        if in_range {
-                            collected_metrics.push(msg.clone());
-                        }
-                    }
-
-                    current_requests += 1;
-
-                    if msg.status_code == expected_status_code {
-                        successful_requests += 1;
-                    }
+                batch = metrics_rx.recv() => {
+                    // Every sender is gone: the test is over, or could not start.
+                    let Some(batch) = batch else {
+                        let _ = shutdown_tx_main.send(1);
+                        break;
+                    };
 
-                    latency_window.push_back((now, latency_ms));
-                    while latency_window.front().map_or(false, |(ts, _)| now.duration_since(*ts) > Duration::from_secs(10)) {
-                        latency_window.pop_front();
-                    }
+                    ingest(&mut aggregate, &mut rate_window, start_time, vec![batch]);
+                },
+                _ = collect_interval.tick() => ingest(&mut aggregate, &mut rate_window, start_time, sink.take_batches()),
+                _ = ui_interval.tick(), if deadline.is_none() => {
+                    let now = Instant::now();
+                    let elapsed_time = now.duration_since(start_time);
 
-                    if let Some((ts, count)) = rps_window.back_mut() {
-                        if now.duration_since(*ts) < Duration::from_millis(100) {
-                            *count += 1;
-                        } else {
-                            rps_window.push_back((now, 1));
-                        }
-                    } else {
-                        rps_window.push_back((now, 1));
-                    }
+                    let last_second = rate_window
+                        .iter()
+                        .filter(|bucket| now.duration_since(bucket.start) <= Duration::from_secs(1));
 
-                    while rps_window.front().map_or(false, |(ts, _)| now.duration_since(*ts) > Duration::from_secs(60)) {
-                        rps_window.pop_front();
+                    let (mut rps, mut sent_per_sec, mut received_per_sec) = (0.0, 0.0, 0.0);
+                    for bucket in last_second {
+                        rps += bucket.requests as f64;
+                        sent_per_sec += bucket.bytes_sent as f64;
+                        received_per_sec += bucket.bytes_received as f64;
                     }
 
-                    let rps: f64 = rps_window
-                        .iter()
-                        .filter(|(ts, _)| now.duration_since(*ts) <= Duration::from_secs(1))
-                        .map(|(_, count)| *count)
-                        .sum::<usize>() as f64;
-
-                    let rpm = rps * 60.0;
+                    let recent_seconds = &aggregate.seconds[aggregate.seconds.len().saturating_sub(UI_HISTORY_SECONDS)..];
 
-                    if last_ui_update.elapsed() >= Duration::from_millis(100) {
-                        let elapsed_time = start_time.elapsed();
-                        let recent_latencies: Vec<(f64, f64)> = latency_window
+                    let _ = ui_tx.send(UiData {
+                        elapsed_time,
+                        current_requests: aggregate.total_requests,
+                        successful_requests: aggregate.successful_requests,
+                        errors: aggregate.error_breakdown(),
+                        missed_slots: missed_slots.load(Ordering::Relaxed),
+                        lost_samples: sink.lost_samples.load(Ordering::Relaxed),
+                        service: LatencyPercentiles::from_histogram(&aggregate.service),
+                        corrected: LatencyPercentiles::from_histogram(&aggregate.corrected),
+                        latency_history: recent_seconds
                             .iter()
-                            .map(|&(ts, latency)| {
-                                let secs_since_start = ts.duration_since(start_time).as_secs_f64();
-                                (secs_since_start, latency)
-                            })
-                            .collect();
-
-                        let _ = ui_tx_clone.send(UiData::new(
-                            elapsed_time,
-                            current_requests,
-                            successful_requests,
-                            recent_latencies,
-                            rps,
-                            rpm,
-                        ));
-
-                        last_ui_update = now;
-                    }
+                            .map(|s| (s.second as f64, s.service.p99))
+                            .collect(),
+                        corrected_latency_history: recent_seconds
+                            .iter()
+                            .map(|s| (s.second as f64, s.corrected.p99))
+                            .collect(),
+                        rps,
+                        rpm: rps * 60.0,
+                        sent_mb_per_sec: sent_per_sec / 1_000_000.0,
+                        received_mb_per_sec: received_per_sec / 1_000_000.0,
+                    });
 
-                    if now.duration_since(start_time) >= target_duration {
+                    if elapsed_time >= target_duration {
                         let _ = shutdown_tx_main.send(1);
-                        break;
                     }
                 },
-                _ = shutdown_rx.recv() => break,
+                _ = shutdown_rx.recv(), if deadline.is_none() => deadline = Some(Instant::now() + drain_limit),
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => break,
             }
         }
 
-        collected_metrics
-    });
+        // Batches already queued and results still waiting with their senders count;
+        // senders that flush from now on find the channel closed.
+        metrics_rx.close();
+        while let Ok(batch) = metrics_rx.try_recv() {
+            aggregate.merge(batch);
+        }
+        for batch in sink.take_batches() {
+            aggregate.merge(batch);
+        }
 
-    (forwarder_handle, metrics_aggregator_handle)
+        aggregate.lost_samples = sink.lost_samples.load(Ordering::Relaxed);
+        aggregate.finish(start_time.elapsed());
+        aggregate
+    })
 }
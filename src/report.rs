@@ -0,0 +1,379 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{args::{SummaryFormat, TesterArgs}, metrics::{LatencyPercentiles, MetricsAggregate}, thresholds::ThresholdResult};
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<String>,
+    pub duration_secs: f64,
+    pub requests: RequestSummary,
+    pub latency_ms: LatencySummary,
+    /// Phases of requests that received a response
+    pub phases_ms: PhaseSummary,
+    pub transfer: TransferSummary,
+    /// Responses per HTTP status code
+    pub status_codes: BTreeMap<u16, u64>,
+    /// Responses per negotiated protocol, e.g. "HTTP/2.0"
+    pub protocols: BTreeMap<String, u64>,
+    /// Failed requests per outcome, including unexpected status codes
+    pub errors: BTreeMap<String, u64>,
+    /// Per scenario request breakdown
+    pub endpoints: Vec<EndpointSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub thresholds: Vec<ThresholdResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestSummary {
+    pub total: u64,
+    pub successful: u64,
+    pub failed: u64,
+    /// Successful requests as a percentage of all requests
+    pub success_rate: f64,
+    pub rps: f64,
+    pub missed_slots: u64,
+    /// Results that never reached the collector and are missing from every other number
+    pub lost_samples: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub service: LatencyPercentiles,
+    pub corrected: LatencyPercentiles,
+}
+
+/// Request and response body bytes, and their average rate over the run.
+#[derive(Debug, Serialize)]
+pub struct TransferSummary {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub sent_mb_per_sec: f64,
+    pub received_mb_per_sec: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhaseSummary {
+    pub dns: LatencyPercentiles,
+    pub connect: LatencyPercentiles,
+    pub tls: LatencyPercentiles,
+    /// Time to first byte, including `dns`, `connect` and `tls`
+    pub ttfb: LatencyPercentiles,
+    pub download: LatencyPercentiles,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EndpointSummary {
+    pub name: String,
+    pub requests: u64,
+    pub successful: u64,
+    pub failed: u64,
+    pub success_rate: f64,
+    pub latency_ms: LatencySummary,
+}
+
+/// Formats a byte count with a decimal unit, e.g. "1.50 MB".
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "kB", "MB", "GB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.2} {}", value, UNITS[unit]) }
+}
+
+fn success_rate(successful: u64, total: u64) -> f64 {
+    if total > 0 { successful as f64 / total as f64 * 100.0 } else { 0.0 }
+}
+
+impl Summary {
+    pub fn new(aggregate: &MetricsAggregate, args: &TesterArgs, missed_slots: u64) -> Self {
+        let duration_secs = aggregate.elapsed.as_secs_f64();
+        let total = aggregate.total_requests;
+        let successful = aggregate.successful_requests;
+        let per_second = |count: u64| if duration_secs > 0.0 { count as f64 / duration_secs } else { 0.0 };
+
+        let errors = aggregate
+            .error_breakdown()
+            .into_iter()
+            .map(|(outcome, count)| (outcome.to_string(), count))
+            .collect();
+
+        let endpoints = aggregate.endpoints
+            .iter()
+            .map(|endpoint| EndpointSummary {
+                name: endpoint.name.clone(),
+                requests: endpoint.requests,
+                successful: endpoint.successful_requests,
+                failed: endpoint.requests - endpoint.successful_requests,
+                success_rate: success_rate(endpoint.successful_requests, endpoint.requests),
+                latency_ms: LatencySummary {
+                    service: LatencyPercentiles::from_histogram(&endpoint.service),
+                    corrected: LatencyPercentiles::from_histogram(&endpoint.corrected),
+                },
+            })
+            .collect();
+
+        Self {
+            url: args.url.clone(),
+            method: args.url.as_ref().map(|_| args.method.to_string()),
+            scenario: args.scenario.clone(),
+            duration_secs,
+            requests: RequestSummary {
+                total,
+                successful,
+                failed: total - successful,
+                success_rate: success_rate(successful, total),
+                rps: per_second(total),
+                missed_slots,
+                lost_samples: aggregate.lost_samples,
+            },
+            latency_ms: LatencySummary {
+                service: LatencyPercentiles::from_histogram(&aggregate.service),
+                corrected: LatencyPercentiles::from_histogram(&aggregate.corrected),
+            },
+            phases_ms: PhaseSummary {
+                dns: LatencyPercentiles::from_histogram(&aggregate.phases.dns),
+                connect: LatencyPercentiles::from_histogram(&aggregate.phases.connect),
+                tls: LatencyPercentiles::from_histogram(&aggregate.phases.tls),
+                ttfb: LatencyPercentiles::from_histogram(&aggregate.phases.ttfb),
+                download: LatencyPercentiles::from_histogram(&aggregate.phases.download),
+            },
+            transfer: TransferSummary {
+                bytes_sent: aggregate.bytes_sent,
+                bytes_received: aggregate.bytes_received,
+                sent_mb_per_sec: per_second(aggregate.bytes_sent) / 1_000_000.0,
+                received_mb_per_sec: per_second(aggregate.bytes_received) / 1_000_000.0,
+            },
+            status_codes: aggregate.status_codes.clone(),
+            protocols: aggregate.versions.iter().map(|(version, count)| (format!("{:?}", version), *count)).collect(),
+            errors,
+            endpoints,
+            thresholds: Vec::new(),
+        }
+    }
+
+    pub async fn write_json(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).await?;
+            }
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+
+    fn target(&self) -> String {
+        match (&self.scenario, &self.method, &self.url) {
+            (Some(scenario), _, _) => format!("scenario {}", scenario),
+            (None, Some(method), Some(url)) => format!("{} {}", method, url),
+            _ => String::new(),
+        }
+    }
+
+    fn lost_warning(&self) -> Option<String> {
+        let lost = self.requests.lost_samples;
+        (lost > 0).then(|| format!("{} results never reached the collector, so the numbers below are incomplete", lost))
+    }
+
+    fn phases(&self) -> [(&'static str, &LatencyPercentiles); 5] {
+        [
+            ("DNS", &self.phases_ms.dns),
+            ("TCP connect", &self.phases_ms.connect),
+            ("TLS handshake", &self.phases_ms.tls),
+            ("TTFB", &self.phases_ms.ttfb),
+            ("Download", &self.phases_ms.download),
+        ]
+    }
+
+    pub fn render(&self, format: SummaryFormat) -> String {
+        match format {
+            SummaryFormat::Text => self.to_text(),
+            SummaryFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        let r = &self.requests;
+
+        let _ = writeln!(out, "Summary for {}", self.target());
+        if let Some(warning) = self.lost_warning() {
+            let _ = writeln!(out, "  Warning: {}", warning);
+        }
+        let _ = writeln!(out, "  Duration:      {:.2}s", self.duration_secs);
+        let _ = writeln!(out, "  Requests:      {} ({} successful, {} failed)", r.total, r.successful, r.failed);
+        let _ = writeln!(out, "  Success rate:  {:.2}%", r.success_rate);
+        let _ = writeln!(out, "  RPS:           {:.2}", r.rps);
+        let _ = writeln!(out, "  Missed slots:  {}", r.missed_slots);
+        let _ = writeln!(out, "  Lost samples:  {}", r.lost_samples);
+        let t = &self.transfer;
+        let _ = writeln!(out, "  Received:      {} ({:.2} MB/s)", format_bytes(t.bytes_received), t.received_mb_per_sec);
+        let _ = writeln!(out, "  Sent:          {} ({:.2} MB/s)", format_bytes(t.bytes_sent), t.sent_mb_per_sec);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "  Latency (ms)   {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}", "Mean", "P50", "P90", "P99", "P99.9", "P99.99", "Max");
+
+        for (label, l) in [("Service", &self.latency_ms.service), ("Corrected", &self.latency_ms.corrected)] {
+            let _ = writeln!(
+                out,
+                "  {:<14} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+                label, l.mean, l.p50, l.p90, l.p99, l.p99_9, l.p99_99, l.max
+            );
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "  Phases (ms)    {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}", "Mean", "P50", "P90", "P99", "P99.9", "P99.99", "Max");
+
+        for (label, l) in self.phases() {
+            let _ = writeln!(
+                out,
+                "  {:<14} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+                label, l.mean, l.p50, l.p90, l.p99, l.p99_9, l.p99_99, l.max
+            );
+        }
+
+        if !self.status_codes.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "  Status codes:");
+            for (code, count) in &self.status_codes {
+                let _ = writeln!(out, "    {}: {}", code, count);
+            }
+        }
+
+        if !self.protocols.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "  Protocols:");
+            for (protocol, count) in &self.protocols {
+                let _ = writeln!(out, "    {}: {}", protocol, count);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "  Errors:");
+            for (outcome, count) in &self.errors {
+                let _ = writeln!(out, "    {}: {}", outcome, count);
+            }
+        }
+
+        if self.endpoints.len() > 1 {
+            let width = self.endpoints.iter().map(|e| e.name.len()).max().unwrap_or(0).max(8);
+            let _ = writeln!(out);
+            let _ = writeln!(out, "  {:<width$} {:>9} {:>9} {:>9} {:>9} {:>9}", "Request", "Count", "Success", "P50 (ms)", "P99 (ms)", "Max (ms)", width = width);
+            for e in &self.endpoints {
+                let l = &e.latency_ms.service;
+                let _ = writeln!(
+                    out,
+                    "  {:<width$} {:>9} {:>8.2}% {:>9.2} {:>9.2} {:>9.2}",
+                    e.name, e.requests, e.success_rate, l.p50, l.p99, l.max,
+                    width = width
+                );
+            }
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let r = &self.requests;
+
+        let _ = writeln!(out, "## Summary for `{}`", self.target());
+        let _ = writeln!(out);
+        if let Some(warning) = self.lost_warning() {
+            let _ = writeln!(out, "> **Warning:** {}", warning);
+            let _ = writeln!(out);
+        }
+        let _ = writeln!(out, "| Metric | Value |");
+        let _ = writeln!(out, "| --- | ---: |");
+        let _ = writeln!(out, "| Duration | {:.2}s |", self.duration_secs);
+        let _ = writeln!(out, "| Requests | {} |", r.total);
+        let _ = writeln!(out, "| Successful | {} |", r.successful);
+        let _ = writeln!(out, "| Failed | {} |", r.failed);
+        let _ = writeln!(out, "| Success rate | {:.2}% |", r.success_rate);
+        let _ = writeln!(out, "| RPS | {:.2} |", r.rps);
+        let _ = writeln!(out, "| Missed slots | {} |", r.missed_slots);
+        let _ = writeln!(out, "| Lost samples | {} |", r.lost_samples);
+        let t = &self.transfer;
+        let _ = writeln!(out, "| Received | {} ({:.2} MB/s) |", format_bytes(t.bytes_received), t.received_mb_per_sec);
+        let _ = writeln!(out, "| Sent | {} ({:.2} MB/s) |", format_bytes(t.bytes_sent), t.sent_mb_per_sec);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "| Latency (ms) | Mean | P50 | P90 | P99 | P99.9 | P99.99 | Max |");
+        let _ = writeln!(out, "| --- | ---: | ---: | ---: | ---: | ---: | ---: | ---: |");
+
+        for (label, l) in [("Service", &self.latency_ms.service), ("Corrected", &self.latency_ms.corrected)] {
+            let _ = writeln!(
+                out,
+                "| {} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} |",
+                label, l.mean, l.p50, l.p90, l.p99, l.p99_9, l.p99_99, l.max
+            );
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "| Phase (ms) | Mean | P50 | P90 | P99 | P99.9 | P99.99 | Max |");
+        let _ = writeln!(out, "| --- | ---: | ---: | ---: | ---: | ---: | ---: | ---: |");
+
+        for (label, l) in self.phases() {
+            let _ = writeln!(
+                out,
+                "| {} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} |",
+                label, l.mean, l.p50, l.p90, l.p99, l.p99_9, l.p99_99, l.max
+            );
+        }
+
+        if !self.status_codes.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "| Status code | Count |");
+            let _ = writeln!(out, "| --- | ---: |");
+            for (code, count) in &self.status_codes {
+                let _ = writeln!(out, "| {} | {} |", code, count);
+            }
+        }
+
+        if !self.protocols.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "| Protocol | Count |");
+            let _ = writeln!(out, "| --- | ---: |");
+            for (protocol, count) in &self.protocols {
+                let _ = writeln!(out, "| {} | {} |", protocol, count);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "| Error | Count |");
+            let _ = writeln!(out, "| --- | ---: |");
+            for (outcome, count) in &self.errors {
+                let _ = writeln!(out, "| {} | {} |", outcome, count);
+            }
+        }
+
+        if self.endpoints.len() > 1 {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "| Request | Count | Success rate | P50 (ms) | P99 (ms) | Max (ms) |");
+            let _ = writeln!(out, "| --- | ---: | ---: | ---: | ---: | ---: |");
+            for e in &self.endpoints {
+                let l = &e.latency_ms.service;
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {:.2}% | {:.2} | {:.2} | {:.2} |",
+                    e.name, e.requests, e.success_rate, l.p50, l.p99, l.max
+                );
+            }
+        }
+
+        out
+    }
+}
@@ -0,0 +1,141 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::args::{HttpMethod, TesterArgs};
+
+/// One kind of request sent during a test, either from the command line or a scenario file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestDefinition {
+    pub name: String,
+    #[serde(default = "default_method", deserialize_with = "deserialize_method")]
+    pub method: HttpMethod,
+    pub url: String,
+    #[serde(default, deserialize_with = "deserialize_pairs")]
+    pub headers: Vec<(String, String)>,
+    /// Template text, or '@path' to read it from a file
+    #[serde(default)]
+    pub body: String,
+    /// Send `body` byte for byte, without placeholders
+    #[serde(default)]
+    pub binary: bool,
+    /// URL-encoded form fields, instead of `body`
+    #[serde(default, deserialize_with = "deserialize_pairs")]
+    pub form: Vec<(String, String)>,
+    /// Multipart form fields, instead of `body`. Values starting with '@' upload that file
+    #[serde(default, deserialize_with = "deserialize_pairs")]
+    pub multipart: Vec<(String, String)>,
+    /// Relative share of traffic compared to the other requests
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Falls back to --status when not set
+    pub expected_status: Option<u16>,
+    /// Variables to set from the JSON response body, as `name: $.json.path`
+    #[serde(default)]
+    pub extract: BTreeMap<String, String>,
+    /// Checks in `--assert` syntax, applied in addition to the ones given on the command line
+    #[serde(default)]
+    pub assert: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    /// Independent requests picked by weight for every iteration
+    #[serde(default)]
+    pub requests: Vec<RequestDefinition>,
+    /// Steps every virtual user runs in order, sharing extracted variables
+    #[serde(default)]
+    pub flow: Vec<RequestDefinition>,
+}
+
+fn default_method() -> HttpMethod {
+    HttpMethod(reqwest::Method::GET)
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn deserialize_method<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HttpMethod, D::Error> {
+    let method = String::deserialize(deserializer)?;
+    method.parse().map_err(serde::de::Error::custom)
+}
+
+fn deserialize_pairs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+    Ok(BTreeMap::<String, String>::deserialize(deserializer)?.into_iter().collect())
+}
+
+impl Scenario {
+    /// Loads the scenario file given with --scenario, or builds a single request from the other flags.
+    pub fn from_args(args: &TesterArgs) -> Result<Self, String> {
+        let scenario = match (&args.scenario, &args.url) {
+            (Some(path), _) => Self::load(path)?,
+            (None, Some(url)) => Scenario {
+                requests: vec![RequestDefinition {
+                    name: url.clone(),
+                    method: args.method.clone(),
+                    url: url.clone(),
+                    headers: args.headers.clone(),
+                    body: args.data_binary.clone().unwrap_or_else(|| args.data.clone()),
+                    binary: args.data_binary.is_some(),
+                    form: args.form_fields.clone(),
+                    multipart: args.multipart.clone(),
+                    weight: 1,
+                    expected_status: None,
+                    extract: BTreeMap::new(),
+                    assert: Vec::new(),
+                }],
+                flow: Vec::new(),
+            },
+            (None, None) => return Err("Either --url or --scenario is required".to_string()),
+        };
+
+        match (scenario.requests.is_empty(), scenario.flow.is_empty()) {
+            (true, true) => return Err("Scenario does not define any requests".to_string()),
+            (false, false) => return Err("Scenario can define either 'requests' or 'flow', not both".to_string()),
+            _ => {}
+        }
+
+        for definition in scenario.definitions() {
+            let bodies = [!definition.body.is_empty(), !definition.form.is_empty(), !definition.multipart.is_empty()];
+            if bodies.iter().filter(|set| **set).count() > 1 {
+                return Err(format!("Request '{}' can only have one of 'body', 'form' or 'multipart'", definition.name));
+            }
+        }
+
+        if !scenario.is_flow() && scenario.requests.iter().all(|r| r.weight == 0) {
+            return Err("At least one scenario request needs a weight above 0".to_string());
+        }
+
+        Ok(scenario)
+    }
+
+    fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scenario '{}': {}", path, e))?;
+
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "toml" => toml::from_str(&content).map_err(|e| format!("Invalid scenario '{}': {}", path, e)),
+            _ => serde_yaml::from_str(&content).map_err(|e| format!("Invalid scenario '{}': {}", path, e)),
+        }
+    }
+
+    pub fn is_flow(&self) -> bool {
+        !self.flow.is_empty()
+    }
+
+    /// The flow steps or the weighted requests, whichever the scenario defines.
+    pub fn definitions(&self) -> &[RequestDefinition] {
+        if self.is_flow() { &self.flow } else { &self.requests }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.definitions().iter().map(|r| r.name.clone()).collect()
+    }
+}
@@ -2,12 +2,24 @@ use std::time::Duration;
 
 use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyModifiers};
 use tokio::sync::broadcast;
+use tracing::{error, info, warn};
 
+use crate::ui::{Ui, UiActions};
 
-pub fn setup_shutdown_handler(shutdown_tx: &broadcast::Sender<u16>) -> tokio::task::JoinHandle<()> {
+
+/// Stops the test on Ctrl+C in the dashboard. It puts the terminal in raw mode, so Ctrl+C arrives as a
+/// key event there instead of SIGINT. When `headless` is set there is no dashboard, and this only
+/// waits for the shutdown.
+pub fn setup_shutdown_handler(shutdown_tx: &broadcast::Sender<u16>, headless: bool) -> tokio::task::JoinHandle<()> {
     let shutdown_tx = shutdown_tx.clone();
     let mut shutdown_rx = shutdown_tx.subscribe();
 
+    if headless {
+        return tokio::spawn(async move {
+            let _ = shutdown_rx.recv().await;
+        });
+    }
+
     tokio::spawn(async move {
         tokio::select! {
             _ = tokio::task::spawn_blocking(move || {
@@ -25,7 +37,92 @@ pub fn setup_shutdown_handler(shutdown_tx: &broadcast::Sender<u16>) -> tokio::ta
                 }
             }) => {},
 
-            _ = shutdown_rx.recv() => return
+            _ = shutdown_rx.recv() => {}
         }
     })
 }
+
+/// Stops the test on SIGINT, SIGTERM or SIGHUP, e.g. from `kill` or a terminating container. Once the
+/// test is stopping, for whatever reason, another signal exits immediately without results.
+///
+/// The task outlives the shutdown so it can still force the exit while charts are plotted, and is
+/// therefore not joined.
+pub fn setup_signal_handler(shutdown_tx: &broadcast::Sender<u16>, headless: bool) {
+    let shutdown_tx = shutdown_tx.clone();
+    let mut shutdown_rx = shutdown_tx.subscribe();
+
+    tokio::spawn(async move {
+        let mut signals = match Signals::new() {
+            Ok(signals) => signals,
+            Err(e) => {
+                error!("Failed to listen for signals: {}", e);
+                return;
+            }
+        };
+        let mut stopping = false;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv(), if !stopping => stopping = true,
+                (name, exit_code) = signals.recv() => {
+                    if stopping {
+                        warn!("Received {} again, exiting immediately", name);
+                        if !headless {
+                            Ui::cleanup();
+                        }
+                        std::process::exit(exit_code);
+                    }
+
+                    info!("Received {}, stopping the test. Send it again to exit immediately", name);
+                    stopping = true;
+                    let _ = shutdown_tx.send(1);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+struct Signals {
+    interrupt: tokio::signal::unix::Signal,
+    terminate: tokio::signal::unix::Signal,
+    hangup: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> std::io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    /// Waits for the next signal. Returns its name and the conventional exit code of 128 plus its number.
+    async fn recv(&mut self) -> (&'static str, i32) {
+        tokio::select! {
+            _ = self.interrupt.recv() => ("SIGINT", 130),
+            _ = self.terminate.recv() => ("SIGTERM", 143),
+            _ = self.hangup.recv() => ("SIGHUP", 129),
+        }
+    }
+}
+
+/// Only Ctrl+C can be observed outside of Unix.
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self) -> (&'static str, i32) {
+        let _ = tokio::signal::ctrl_c().await;
+        ("Ctrl+C", 130)
+    }
+}
@@ -0,0 +1,138 @@
+use std::{collections::HashMap, sync::atomic::{AtomicU64, Ordering}, time::{SystemTime, UNIX_EPOCH}};
+
+use rand::Rng;
+
+/// Per virtual user variables, filled by extraction rules and read by templates.
+/// Every worker starts with `vu_id` set.
+pub type Variables = HashMap<String, String>;
+
+/// Variables of a new virtual user before anything was extracted.
+pub fn new_variables(vu_id: u64) -> Variables {
+    HashMap::from([("vu_id".to_string(), vu_id.to_string())])
+}
+
+/// Shared by every `{{seq}}` placeholder, so values are unique across the whole run.
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Variable(String),
+    Uuid,
+    RandomInt { min: i64, max: i64 },
+    Seq,
+    /// Milliseconds since the Unix epoch
+    Timestamp,
+}
+
+impl Part {
+    /// Parses the inside of a placeholder: a generator such as `random_int 1 100`, or a variable name.
+    fn parse(placeholder: &str) -> Result<Self, String> {
+        let mut words = placeholder.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+
+        let no_args = |part: Part| {
+            if args.is_empty() {
+                Ok(part)
+            } else {
+                Err(format!("'{{{{{}}}}}' does not take arguments", name))
+            }
+        };
+
+        match name {
+            "uuid" => no_args(Part::Uuid),
+            "seq" => no_args(Part::Seq),
+            "timestamp" => no_args(Part::Timestamp),
+            "random_int" => {
+                let invalid = || format!("Expected '{{{{random_int <min> <max>}}}}', got '{{{{{}}}}}'", placeholder);
+                let [min, max] = args.as_slice() else {
+                    return Err(invalid());
+                };
+                let min: i64 = min.parse().map_err(|_| invalid())?;
+                let max: i64 = max.parse().map_err(|_| invalid())?;
+                if min > max {
+                    return Err(invalid());
+                }
+                Ok(Part::RandomInt { min, max })
+            }
+            _ => no_args(Part::Variable(name.to_string())),
+        }
+    }
+}
+
+/// A string with `{{name}}` placeholders, parsed once and rendered for every request.
+/// Besides variables, placeholders can be generators: `{{uuid}}`, `{{random_int 1 1000}}`,
+/// `{{seq}}` and `{{timestamp}}`. `\{{` is a literal `{{`.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+
+        while let Some(open) = rest.find("{{") {
+            if rest[..open].ends_with('\\') {
+                parts.push(Part::Literal(format!("{}{{{{", &rest[..open - 1])));
+                rest = &rest[open + 2..];
+                continue;
+            }
+
+            let close = rest[open + 2..]
+                .find("}}")
+                .map(|i| open + 2 + i)
+                .ok_or_else(|| format!("Unclosed '{{{{' in template '{}'", source))?;
+
+            let name = rest[open + 2..close].trim();
+            if name.is_empty() {
+                return Err(format!("Empty placeholder in template '{}'", source));
+            }
+
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            parts.push(Part::parse(name)?);
+            rest = &rest[close + 2..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Names of the variables the template reads, generators excluded.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn render(&self, vars: &Variables) -> Result<String, String> {
+        let mut out = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Variable(name) => match vars.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => return Err(format!("Variable '{}' is not set", name)),
+                },
+                Part::Uuid => out.push_str(&uuid::Uuid::new_v4().to_string()),
+                Part::RandomInt { min, max } => out.push_str(&rand::thread_rng().gen_range(*min..=*max).to_string()),
+                Part::Seq => out.push_str(&SEQUENCE.fetch_add(1, Ordering::Relaxed).to_string()),
+                Part::Timestamp => {
+                    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+                    out.push_str(&millis.to_string());
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::{metrics::LatencyPercentiles, report::Summary};
+
+/// Exit code used when at least one threshold is breached.
+pub const THRESHOLDS_FAILED_EXIT_CODE: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Percentile {
+    Mean,
+    P50,
+    P90,
+    P99,
+    P99_9,
+    P99_99,
+    Max,
+}
+
+impl Percentile {
+    fn value(&self, latency: &LatencyPercentiles) -> f64 {
+        match self {
+            Percentile::Mean => latency.mean,
+            Percentile::P50 => latency.p50,
+            Percentile::P90 => latency.p90,
+            Percentile::P99 => latency.p99,
+            Percentile::P99_9 => latency.p99_9,
+            Percentile::P99_99 => latency.p99_99,
+            Percentile::Max => latency.max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ThresholdMetric {
+    /// Service time, or corrected response time when `corrected` is set (milliseconds)
+    Latency { percentile: Percentile, corrected: bool },
+    ErrorRate,
+    SuccessRate,
+    Rps,
+    Requests,
+    MissedSlots,
+}
+
+impl ThresholdMetric {
+    fn parse(name: &str) -> Result<Self, String> {
+        let (name, corrected) = match name.strip_prefix("corrected_") {
+            Some(rest) => (rest, true),
+            None => (name, false),
+        };
+
+        let percentile = match name {
+            "mean" | "avg" => Some(Percentile::Mean),
+            "p50" | "median" => Some(Percentile::P50),
+            "p90" => Some(Percentile::P90),
+            "p99" => Some(Percentile::P99),
+            "p99.9" => Some(Percentile::P99_9),
+            "p99.99" => Some(Percentile::P99_99),
+            "max" => Some(Percentile::Max),
+            _ => None,
+        };
+
+        if let Some(percentile) = percentile {
+            return Ok(ThresholdMetric::Latency { percentile, corrected });
+        }
+
+        if corrected {
+            return Err(format!("'corrected_' only applies to latency metrics, not '{}'", name));
+        }
+
+        match name {
+            "error_rate" => Ok(ThresholdMetric::ErrorRate),
+            "success_rate" => Ok(ThresholdMetric::SuccessRate),
+            "rps" => Ok(ThresholdMetric::Rps),
+            "requests" => Ok(ThresholdMetric::Requests),
+            "missed_slots" => Ok(ThresholdMetric::MissedSlots),
+            _ => Err(format!(
+                "Unknown metric '{}'. Expected one of: mean, p50, p90, p99, p99.9, p99.99, max \
+                 (optionally prefixed with 'corrected_'), error_rate, success_rate, rps, requests, missed_slots",
+                name
+            )),
+        }
+    }
+
+    /// Parses the right-hand side of a threshold into the unit `actual` reports.
+    fn parse_value(&self, value: &str) -> Result<f64, String> {
+        let number = |s: &str| s.trim().parse::<f64>().map_err(|_| format!("Invalid threshold value '{}'", value));
+
+        match self {
+            ThresholdMetric::Latency { .. } => {
+                if let Some(us) = value.strip_suffix("us") {
+                    Ok(number(us)? / 1000.0)
+                } else if let Some(ms) = value.strip_suffix("ms") {
+                    number(ms)
+                } else if let Some(s) = value.strip_suffix('s') {
+                    Ok(number(s)? * 1000.0)
+                } else {
+                    number(value)
+                }
+            }
+            ThresholdMetric::ErrorRate | ThresholdMetric::SuccessRate => {
+                number(value.strip_suffix('%').unwrap_or(value))
+            }
+            _ => number(value),
+        }
+    }
+
+    fn actual(&self, summary: &Summary) -> f64 {
+        let requests = &summary.requests;
+
+        match self {
+            ThresholdMetric::Latency { percentile, corrected: false } => percentile.value(&summary.latency_ms.service),
+            ThresholdMetric::Latency { percentile, corrected: true } => percentile.value(&summary.latency_ms.corrected),
+            ThresholdMetric::ErrorRate => {
+                if requests.total > 0 { 100.0 - requests.success_rate } else { 0.0 }
+            }
+            ThresholdMetric::SuccessRate => requests.success_rate,
+            ThresholdMetric::Rps => requests.rps,
+            ThresholdMetric::Requests => requests.total as f64,
+            ThresholdMetric::MissedSlots => requests.missed_slots as f64,
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            ThresholdMetric::Latency { .. } => "ms",
+            ThresholdMetric::ErrorRate | ThresholdMetric::SuccessRate => "%",
+            _ => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn holds(&self, actual: f64, expected: f64) -> bool {
+        match self {
+            Comparison::Less => actual < expected,
+            Comparison::LessOrEqual => actual <= expected,
+            Comparison::Greater => actual > expected,
+            Comparison::GreaterOrEqual => actual >= expected,
+        }
+    }
+}
+
+/// A pass/fail condition on the final metrics, e.g. `p99<250ms`, `error_rate<1%` or `rps>500`.
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    expression: String,
+    metric: ThresholdMetric,
+    comparison: Comparison,
+    value: f64,
+}
+
+impl std::str::FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+
+        let (index, comparison, op_len) = [
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ]
+        .iter()
+        .find_map(|(op, comparison)| expression.find(op).map(|index| (index, *comparison, op.len())))
+        .ok_or_else(|| format!("Invalid threshold '{}'. Expected '<metric><op><value>', e.g. 'p99<250ms'", s))?;
+
+        let metric = ThresholdMetric::parse(&expression[..index].to_lowercase())?;
+        let value = metric.parse_value(&expression[index + op_len..].to_lowercase())?;
+
+        Ok(Threshold {
+            expression,
+            metric,
+            comparison,
+            value,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdResult {
+    pub threshold: String,
+    pub actual: f64,
+    pub unit: &'static str,
+    pub passed: bool,
+}
+
+impl Threshold {
+    /// Every threshold fails for a run without a single request, e.g. against a target that is down,
+    /// since rates and latencies of nothing would otherwise pass.
+    pub fn evaluate(&self, summary: &Summary) -> ThresholdResult {
+        let actual = self.metric.actual(summary);
+
+        ThresholdResult {
+            threshold: self.expression.clone(),
+            actual,
+            unit: self.metric.unit(),
+            passed: summary.requests.total > 0 && self.comparison.holds(actual, self.value),
+        }
+    }
+}
+
+pub fn render_results(results: &[ThresholdResult]) -> String {
+    let mut out = String::new();
+    let width = results.iter().map(|r| r.threshold.len()).max().unwrap_or(0);
+
+    let _ = writeln!(out, "Thresholds:");
+    for result in results {
+        let _ = writeln!(
+            out,
+            "  {} {:<width$}  actual: {:.2}{}",
+            if result.passed { "✅ PASS" } else { "❌ FAIL" },
+            result.threshold,
+            result.actual,
+            result.unit,
+            width = width,
+        );
+    }
+
+    out
+}
@@ -3,22 +3,17 @@ use crossterm::{execute, terminal::{enable_raw_mode, disable_raw_mode, EnterAlte
 use ratatui::{backend::CrosstermBackend, layout::{Constraint, Direction, Layout}, prelude::{text, Backend}, style::{Color, Style}, text::Span, widgets::{Block, Borders, Paragraph, Wrap}, Terminal};
 use std::time::Duration;
 use std::io;
-use tokio::sync::{broadcast::{self}, watch};
+use tokio::{sync::{broadcast::{self}, watch}, time::{interval_at, Instant}};
 
-use crate::args::TesterArgs;
+use crate::{args::TesterArgs, metrics::{LatencyPercentiles, Outcome}};
     
 pub trait UiActions {
     fn setup_terminal() -> Result<Terminal<CrosstermBackend<std::io::Stdout>>, Box<dyn Error>>;
     fn cleanup();
     fn render<B: Backend>(
         terminal: &mut Terminal<B>,
-        elapsed_time: &Duration,
-        current_request: &u64,
-        successful_requests: &u64,
+        data: &UiData,
         target_duration: &u64,
-        latencies: &Vec<(f64, f64)>,
-        rps: &f64,
-        rpm: &f64
     );
 }
 
@@ -38,14 +33,26 @@ impl UiActions for Ui {
 
     fn render<B: Backend>(
     terminal: &mut Terminal<B>,
-    elapsed_time: &Duration,
-    current_request: &u64,
-    successful_requests: &u64,
+    data: &UiData,
     target_duration: &u64,
-    latencies: &Vec<(f64, f64)>,
-    rps: &f64,
-    rpm: &f64
 ) {
+    let UiData {
+        elapsed_time,
+        current_requests,
+        successful_requests,
+        errors,
+        missed_slots,
+        lost_samples,
+        service,
+        corrected,
+        latency_history,
+        corrected_latency_history,
+        rps,
+        rpm,
+        sent_mb_per_sec,
+        received_mb_per_sec,
+    } = data;
+
     terminal.draw(|f| {
         let size = f.size();
 
@@ -53,7 +60,7 @@ impl UiActions for Ui {
             .direction(Direction::Vertical)
             .margin(1)
             .constraints([
-                Constraint::Length(5),
+                Constraint::Length(6),
                 Constraint::Length(5),
                 Constraint::Min(10),
             ])
@@ -68,58 +75,84 @@ impl UiActions for Ui {
             ]),
             text::Line::from(vec![
                 Span::from("Requests: "),
-                Span::styled(current_request.to_string(), Style::default().fg(Color::LightBlue)),
+                Span::styled(current_requests.to_string(), Style::default().fg(Color::LightBlue)),
                 Span::from("   Success: "),
                 Span::styled(successful_requests.to_string(), Style::default().fg(Color::Magenta)),
+                Span::from("   Missed Slots: "),
+                Span::styled(missed_slots.to_string(), Style::default().fg(Color::Red)),
+                Span::from("   Lost: "),
+                Span::styled(lost_samples.to_string(), Style::default().fg(Color::Red)),
             ]),
             text::Line::from(vec![
                 Span::from("RPS: "),
                 Span::styled(format!("{}", rps), Style::default().fg(Color::Cyan)),
                 Span::from("   RPM: "),
                 Span::styled(format!("{}", rpm), Style::default().fg(Color::Cyan)),
+                Span::from("   Received: "),
+                Span::styled(format!("{:.2} MB/s", received_mb_per_sec), Style::default().fg(Color::Cyan)),
+                Span::from("   Sent: "),
+                Span::styled(format!("{:.2} MB/s", sent_mb_per_sec), Style::default().fg(Color::Cyan)),
             ]),
+            errors_line(errors),
         ])
         .block(Block::default().title("Stats").borders(Borders::ALL))
         .wrap(Wrap { trim: true });
 
         f.render_widget(stats_text, chunks[0]);
 
-        let mut lat_values: Vec<f64> = latencies.iter().map(|(_, latency)| *latency).collect();
-        lat_values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-
-        let p50 = percentile(&lat_values, 0.50);
-        let p90 = percentile(&lat_values, 0.90);
-        let p99 = percentile(&lat_values, 0.99);
-
-        let percentiles_text = Paragraph::new(vec![text::Line::from(vec![
+        let percentiles_line = |label: &'static str, values: &LatencyPercentiles| text::Line::from(vec![
+            Span::from(label),
             Span::from("P50: "),
-            Span::styled(format!("{:.2}ms", p50), Style::default().fg(Color::Green)),
+            Span::styled(format!("{:.2}ms", values.p50), Style::default().fg(Color::Green)),
             Span::from("   P90: "),
-            Span::styled(format!("{:.2}ms", p90), Style::default().fg(Color::Yellow)),
+            Span::styled(format!("{:.2}ms", values.p90), Style::default().fg(Color::Yellow)),
             Span::from("   P99: "),
-            Span::styled(format!("{:.2}ms", p99), Style::default().fg(Color::Red)),
-        ])])
+            Span::styled(format!("{:.2}ms", values.p99), Style::default().fg(Color::Red)),
+            Span::from("   P99.99: "),
+            Span::styled(format!("{:.2}ms", values.p99_99), Style::default().fg(Color::Red)),
+            Span::from("   Max: "),
+            Span::styled(format!("{:.2}ms", values.max), Style::default().fg(Color::LightRed)),
+        ]);
+
+        let percentiles_text = Paragraph::new(vec![
+            percentiles_line("Service    ", service),
+            percentiles_line("Corrected  ", corrected),
+        ])
         .block(Block::default().title("Latency Percentiles").borders(Borders::ALL))
         .wrap(Wrap { trim: true });
 
         f.render_widget(percentiles_text, chunks[1]);
 
-        let data_points: Vec<(f64, f64)> = latencies.clone();
-        let y_max = lat_values.iter().cloned().fold(0.0, f64::max).ceil().max(10.0);
-        let x_max = data_points.last().map(|(x, _)| *x).unwrap_or(0.0).ceil();
-        let x_min = if x_max > 10.0 { x_max - 10.0 } else { 0.0 };
+        let y_max = latency_history
+            .iter()
+            .chain(corrected_latency_history.iter())
+            .map(|(_, latency)| *latency)
+            .fold(0.0, f64::max)
+            .ceil()
+            .max(10.0);
+        let x_max = latency_history.last().map(|(x, _)| *x).unwrap_or(0.0).ceil();
+        let x_min = latency_history.first().map(|(x, _)| *x).unwrap_or(0.0).floor();
 
-        let datasets = vec![ratatui::widgets::Dataset::default()
-            .name("Latency Chart")
-            .marker(ratatui::symbols::Marker::Dot)
-            .style(Style::default().fg(Color::Cyan))
-            .data(&data_points)];
+        let datasets = vec![
+            ratatui::widgets::Dataset::default()
+                .name("Corrected P99")
+                .marker(ratatui::symbols::Marker::Braille)
+                .graph_type(ratatui::widgets::GraphType::Line)
+                .style(Style::default().fg(Color::Magenta))
+                .data(corrected_latency_history),
+            ratatui::widgets::Dataset::default()
+                .name("Service P99")
+                .marker(ratatui::symbols::Marker::Braille)
+                .graph_type(ratatui::widgets::GraphType::Line)
+                .style(Style::default().fg(Color::Cyan))
+                .data(latency_history),
+        ];
 
         let chart = ratatui::widgets::Chart::new(datasets)
             .block(Block::default().borders(Borders::ALL))
             .x_axis(
                 ratatui::widgets::Axis::default()
-                    .title("Window Second")
+                    .title("Elapsed Second")
                     .style(Style::default().fg(Color::Gray))
                     .bounds([x_min, x_max])
                     .labels(vec![
@@ -146,19 +179,22 @@ impl UiActions for Ui {
 }
 }
 
-fn percentile(data: &[f64], percentile: f64) -> f64 {
-    if data.is_empty() {
-        return 0.0;
+fn errors_line(errors: &[(Outcome, u64)]) -> text::Line<'static> {
+    let mut spans = vec![Span::from("Errors: ")];
+
+    if errors.is_empty() {
+        spans.push(Span::styled("none", Style::default().fg(Color::Green)));
     }
-    let rank = percentile * (data.len() - 1) as f64;
-    let lower = rank.floor() as usize;
-    let upper = rank.ceil() as usize;
-    if lower == upper {
-        data[lower]
-    } else {
-        let weight = rank - lower as f64;
-        data[lower] * (1.0 - weight) + data[upper] * weight
+
+    for (i, (outcome, count)) in errors.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::from("   "));
+        }
+        spans.push(Span::from(format!("{}: ", outcome)));
+        spans.push(Span::styled(count.to_string(), Style::default().fg(Color::Red)));
     }
+
+    text::Line::from(spans)
 }
 
 #[derive(Debug, Clone)]
@@ -166,40 +202,90 @@ pub struct UiData {
     pub elapsed_time: Duration,
     pub current_requests: u64,
     pub successful_requests: u64,
-    pub latencies: Vec<(f64, f64)>,
+    /// Count per error outcome, most frequent first
+    pub errors: Vec<(Outcome, u64)>,
+    pub missed_slots: u64,
+    /// Results that never reached the collector, so every other number is incomplete
+    pub lost_samples: u64,
+    pub service: LatencyPercentiles,
+    pub corrected: LatencyPercentiles,
+    /// Per-second service time P99 of the most recent seconds
+    pub latency_history: Vec<(f64, f64)>,
+    /// Per-second corrected response time P99 of the most recent seconds
+    pub corrected_latency_history: Vec<(f64, f64)>,
     pub rps: f64,
     pub rpm: f64,
+    /// Request body throughput over the last second
+    pub sent_mb_per_sec: f64,
+    /// Response body throughput over the last second
+    pub received_mb_per_sec: f64,
 }
 
 impl UiData {
-    pub fn new(
-        elapsed_time: Duration,
-        current_requests: u64,
-        successful_requests: u64,
-        latencies: Vec<(f64, f64)>,
-        rps: f64,
-        rpm: f64,
-    ) -> Self {
-        Self {
-            elapsed_time,
-            current_requests,
-            successful_requests,
-            latencies,
-            rps,
-            rpm,
-        }
-    }
-
     pub fn default() -> Self {
         Self {
             elapsed_time: Duration::from_secs(0),
             current_requests: 0,
             successful_requests: 0,
-            latencies: Vec::new(),
+            errors: Vec::new(),
+            missed_slots: 0,
+            lost_samples: 0,
+            service: LatencyPercentiles::default(),
+            corrected: LatencyPercentiles::default(),
+            latency_history: Vec::new(),
+            corrected_latency_history: Vec::new(),
             rps: 0.0,
             rpm: 0.0,
+            sent_mb_per_sec: 0.0,
+            received_mb_per_sec: 0.0,
+        }
+    }
+}
+
+/// Prints a progress line to stderr every `--progress-interval`, for runs without a terminal.
+pub fn setup_progress_output(
+    args: &TesterArgs,
+    shutdown_tx: &broadcast::Sender<u16>,
+    ui_tx: &watch::Sender<UiData>,
+) -> tokio::task::JoinHandle<()> {
+    let ui_rx = ui_tx.subscribe();
+    let mut shutdown_rx = shutdown_tx.subscribe();
+    let period = args.progress_interval.max(Duration::from_millis(100));
+
+    tokio::spawn(async move {
+        let mut ticks = interval_at(Instant::now() + period, period);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = ticks.tick() => eprintln!("{}", progress_line(&ui_rx.borrow())),
+            }
         }
+    })
+}
+
+fn progress_line(data: &UiData) -> String {
+    let error_rate = if data.current_requests > 0 {
+        (data.current_requests - data.successful_requests) as f64 / data.current_requests as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    let mut line = format!(
+        "[{:>7.1}s] requests: {}  rps: {:.0}  errors: {:.2}%  p50: {:.2}ms  p99: {:.2}ms",
+        data.elapsed_time.as_secs_f64(),
+        data.current_requests,
+        data.rps,
+        error_rate,
+        data.service.p50,
+        data.service.p99,
+    );
+
+    if data.lost_samples > 0 {
+        line.push_str(&format!("  lost: {}", data.lost_samples));
     }
+
+    line
 }
 
 pub fn setup_render_ui(
@@ -222,16 +308,7 @@ pub fn setup_render_ui(
                 }
                 Ok(_) = ui_rx.changed() => {
                     let msg = ui_rx.borrow().clone();
-                    Ui::render(
-                        &mut terminal,
-                        &msg.elapsed_time,
-                        &msg.current_requests,
-                        &msg.successful_requests,
-                        &target_duration,
-                        &msg.latencies,
-                        &msg.rps,
-                        &msg.rpm
-                    );
+                    Ui::render(&mut terminal, &msg, &target_duration);
                 }
             }
         }